@@ -0,0 +1,127 @@
+use bufkit_data::{BufkitDataErr, Model, StationNumber};
+use chrono::NaiveDateTime;
+use rusqlite::{Connection, OpenFlags};
+use std::{collections::HashMap, path::Path, str::FromStr};
+
+/// Direct access to the archive index for queries and edits the `Archive` doesn't support.
+///
+/// Prefer the `Archive` API whenever it can do the job, this only exists for the operations that
+/// need to read or change the `files` table directly.
+pub struct ArchiveIndex {
+    db_conn: Connection,
+}
+
+/// An entry in the `files` table of the index.
+#[derive(Debug, Clone)]
+pub struct IndexedFile {
+    pub station_num: StationNumber,
+    pub model: Model,
+    pub init_time: NaiveDateTime,
+    pub id: Option<String>,
+    /// The name of the file in the archive's `data` directory.
+    pub file_name: String,
+}
+
+impl ArchiveIndex {
+    pub fn open(root: &Path) -> Result<Self, BufkitDataErr> {
+        let db_file = &root.join("index.db");
+
+        let db_conn = Connection::open_with_flags(db_file, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+
+        Ok(ArchiveIndex { db_conn })
+    }
+
+    /// Get every file in the archive.
+    pub fn files(&self) -> Result<Vec<IndexedFile>, BufkitDataErr> {
+        let mut stmt = self
+            .db_conn
+            .prepare("SELECT station_num, model, init_time, id, file_name FROM files")?;
+
+        let parse_row = |row: &rusqlite::Row| -> Result<IndexedFile, BufkitDataErr> {
+            let station_num: u32 = row.get(0)?;
+            let model: String = row.get(1)?;
+
+            Ok(IndexedFile {
+                station_num: StationNumber::from(station_num),
+                model: Model::from_str(&model)?,
+                init_time: row.get(2)?,
+                id: row.get(3)?,
+                file_name: row.get(4)?,
+            })
+        };
+
+        let results: Result<Vec<IndexedFile>, BufkitDataErr> =
+            stmt.query_and_then([], parse_row)?.collect();
+
+        results
+    }
+
+    /// Get every model run stored for a site.
+    pub fn runs_for(
+        &self,
+        station_num: StationNumber,
+    ) -> Result<Vec<(Model, NaiveDateTime)>, BufkitDataErr> {
+        let station_num: u32 = station_num.into();
+
+        let mut stmt = self.db_conn.prepare(
+            "SELECT model, init_time FROM files WHERE station_num = ?1 ORDER BY model, init_time",
+        )?;
+
+        let parse_row = |row: &rusqlite::Row| -> Result<(Model, NaiveDateTime), BufkitDataErr> {
+            let model: String = row.get(0)?;
+            let init_time: NaiveDateTime = row.get(1)?;
+
+            Ok((Model::from_str(&model)?, init_time))
+        };
+
+        let results: Result<Vec<(Model, NaiveDateTime)>, BufkitDataErr> =
+            stmt.query_and_then([&station_num], parse_row)?.collect();
+
+        results
+    }
+
+    /// Get the newest model run stored for every site and model.
+    pub fn most_recent_runs(
+        &self,
+    ) -> Result<HashMap<(StationNumber, Model), NaiveDateTime>, BufkitDataErr> {
+        let mut stmt = self.db_conn.prepare(
+            "SELECT station_num, model, MAX(init_time) FROM files GROUP BY station_num, model",
+        )?;
+
+        let parse_row = |row: &rusqlite::Row| -> Result<_, BufkitDataErr> {
+            let station_num: u32 = row.get(0)?;
+            let model: String = row.get(1)?;
+            let init_time: NaiveDateTime = row.get(2)?;
+
+            Ok((
+                (StationNumber::from(station_num), Model::from_str(&model)?),
+                init_time,
+            ))
+        };
+
+        let results: Result<HashMap<_, _>, BufkitDataErr> =
+            stmt.query_and_then([], parse_row)?.collect();
+
+        results
+    }
+
+    /// Move all the files for one site to another. The files stay where they are on disk, only
+    /// the index changes.
+    ///
+    /// Fails if both sites have a file for the same model run, remove the duplicates first.
+    pub fn renumber_files(
+        &self,
+        from: StationNumber,
+        to: StationNumber,
+    ) -> Result<usize, BufkitDataErr> {
+        let from: u32 = from.into();
+        let to: u32 = to.into();
+
+        let num_moved = self.db_conn.execute(
+            "UPDATE files SET station_num = ?2 WHERE station_num = ?1",
+            [&from, &to],
+        )?;
+
+        Ok(num_moved)
+    }
+}
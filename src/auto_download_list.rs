@@ -1,12 +1,117 @@
-use bufkit_data::{BufkitDataErr, StationNumber};
-use rusqlite::{Connection, OpenFlags};
-use std::path::Path;
+use bufkit_data::{BufkitDataErr, Model, StationNumber};
+use chrono::{Datelike, NaiveDate};
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+use std::{fmt::Display, path::Path, str::FromStr};
 
+/// The sites to download automatically with bufdn.
+///
+/// The database can hold several named lists, all methods work on the list selected with
+/// `with_list`, which is `DEFAULT_LIST` unless set otherwise.
 pub struct AutoDownloadListDb {
     db_conn: Connection,
+    list: String,
+}
+
+/// A station on an auto download list.
+#[derive(Debug, Clone)]
+pub struct AutoDownloadEntry {
+    pub list: String,
+    pub station_num: StationNumber,
+    /// The models to download for this station, `None` means all of them.
+    pub models: Option<Vec<Model>>,
+    /// When to download this station, `None` means always.
+    pub window: Option<DownloadWindow>,
+}
+
+impl AutoDownloadEntry {
+    /// Whether this entry should be downloaded on the given date.
+    pub fn is_active(&self, date: NaiveDate) -> bool {
+        self.window.is_none_or(|window| window.is_active(date))
+    }
+}
+
+/// The period of time a site on the auto download list is downloaded, both ends are inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownloadWindow {
+    /// A fixed range of dates.
+    Dates { start: NaiveDate, end: NaiveDate },
+    /// A season that recurs every year, as (month, day). It may wrap around the end of the year,
+    /// e.g. from 11-01 to 03-31.
+    Season { start: (u32, u32), end: (u32, u32) },
+}
+
+impl DownloadWindow {
+    /// Parse a window from its start and end. Both must be dates as YYYY-MM-DD or both must be
+    /// days of the year as MM-DD.
+    pub fn parse(start: &str, end: &str) -> Result<Self, BufkitDataErr> {
+        let parse_date = |val: &str| NaiveDate::parse_from_str(val, "%Y-%m-%d").ok();
+
+        // Use a leap year so 02-29 is valid.
+        let parse_day = |val: &str| {
+            NaiveDate::parse_from_str(&format!("2000-{}", val), "%Y-%m-%d")
+                .ok()
+                .map(|date| (date.month(), date.day()))
+        };
+
+        let window = if let (Some(start), Some(end)) = (parse_date(start), parse_date(end)) {
+            if end < start {
+                return Err(BufkitDataErr::GeneralError(format!(
+                    "Download window ends before it starts: {} to {}",
+                    start, end
+                )));
+            }
+            DownloadWindow::Dates { start, end }
+        } else if let (Some(start), Some(end)) = (parse_day(start), parse_day(end)) {
+            DownloadWindow::Season { start, end }
+        } else {
+            return Err(BufkitDataErr::GeneralError(format!(
+                "Invalid download window, use YYYY-MM-DD or MM-DD for both ends: {} to {}",
+                start, end
+            )));
+        };
+
+        Ok(window)
+    }
+
+    /// Whether the window includes the given date.
+    pub fn is_active(&self, date: NaiveDate) -> bool {
+        match *self {
+            DownloadWindow::Dates { start, end } => start <= date && date <= end,
+            DownloadWindow::Season { start, end } => {
+                let day = (date.month(), date.day());
+                if start <= end {
+                    start <= day && day <= end
+                } else {
+                    start <= day || day <= end
+                }
+            }
+        }
+    }
+
+    fn start_string(&self) -> String {
+        match self {
+            DownloadWindow::Dates { start, .. } => start.format("%Y-%m-%d").to_string(),
+            DownloadWindow::Season { start, .. } => format!("{:02}-{:02}", start.0, start.1),
+        }
+    }
+
+    fn end_string(&self) -> String {
+        match self {
+            DownloadWindow::Dates { end, .. } => end.format("%Y-%m-%d").to_string(),
+            DownloadWindow::Season { end, .. } => format!("{:02}-{:02}", end.0, end.1),
+        }
+    }
+}
+
+impl Display for DownloadWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "{} to {}", self.start_string(), self.end_string())
+    }
 }
 
 impl AutoDownloadListDb {
+    pub const DEFAULT_LIST: &'static str = "default";
+
     pub fn open_or_create(root: &Path) -> Result<Self, BufkitDataErr> {
         let db_file = &root.join("auto_download.db");
 
@@ -22,13 +127,92 @@ impl AutoDownloadListDb {
             [],
         )?;
 
+        Self::migrate(&db_auto_dl)?;
+
         Ok(AutoDownloadListDb {
             db_conn: db_auto_dl,
+            list: Self::DEFAULT_LIST.to_owned(),
         })
     }
 
+    /// Select the named list to work with.
+    pub fn with_list<T: AsRef<str>>(self, list: T) -> Self {
+        Self {
+            list: list.as_ref().to_owned(),
+            ..self
+        }
+    }
+
+    /// The name of the selected list.
+    pub fn list(&self) -> &str {
+        &self.list
+    }
+
+    /// Get the names of all the lists with at least one site on them.
+    pub fn list_names(&self) -> Result<Vec<String>, BufkitDataErr> {
+        let mut stmt = self
+            .db_conn
+            .prepare("SELECT DISTINCT list FROM download ORDER BY list")?;
+
+        let results: Result<Vec<String>, BufkitDataErr> = stmt
+            .query_map([], |row| row.get(0))?
+            .map(|res| res.map_err(BufkitDataErr::Database))
+            .collect();
+
+        results
+    }
+
+    /// Bring an older database up to the current schema, the version is kept in `user_version`.
+    fn migrate(db_conn: &Connection) -> Result<(), BufkitDataErr> {
+        let version: i32 = db_conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        if version < 1 {
+            // Comma separated list of models, NULL means all models.
+            db_conn.execute(
+                "ALTER TABLE download ADD COLUMN models TEXT DEFAULT NULL",
+                [],
+            )?;
+            db_conn.pragma_update(None, "user_version", 1)?;
+        }
+
+        if version < 2 {
+            // Named lists, everything already in the database goes on the default list.
+            db_conn.execute_batch(&format!(
+                "BEGIN;
+                CREATE TABLE download_v2 (
+                    list        TEXT NOT NULL,
+                    station_num INT  NOT NULL,
+                    models      TEXT DEFAULT NULL,
+                    PRIMARY KEY (list, station_num)
+                );
+                INSERT INTO download_v2 (list, station_num, models)
+                    SELECT '{}', station_num, models FROM download;
+                DROP TABLE download;
+                ALTER TABLE download_v2 RENAME TO download;
+                PRAGMA user_version = 2;
+                COMMIT;",
+                Self::DEFAULT_LIST
+            ))?;
+        }
+
+        if version < 3 {
+            // Download windows, either YYYY-MM-DD or MM-DD for both ends. NULL means always.
+            db_conn.execute_batch(
+                "BEGIN;
+                ALTER TABLE download ADD COLUMN window_start TEXT DEFAULT NULL;
+                ALTER TABLE download ADD COLUMN window_end TEXT DEFAULT NULL;
+                PRAGMA user_version = 3;
+                COMMIT;",
+            )?;
+        }
+
+        Ok(())
+    }
+
     pub fn get_list(&self) -> Result<Vec<StationNumber>, BufkitDataErr> {
-        let mut stmt = self.db_conn.prepare("SELECT station_num FROM download")?;
+        let mut stmt = self
+            .db_conn
+            .prepare("SELECT station_num FROM download WHERE list = ?1")?;
 
         let parse_row = |row: &rusqlite::Row| -> Result<StationNumber, rusqlite::Error> {
             let station_num: u32 = row.get(0)?;
@@ -37,31 +221,188 @@ impl AutoDownloadListDb {
         };
 
         let results: Result<Vec<StationNumber>, BufkitDataErr> = stmt
-            .query_and_then([], parse_row)?
+            .query_and_then([&self.list], parse_row)?
             .map(|res| res.map_err(BufkitDataErr::Database))
             .collect();
 
         results
     }
 
+    /// Get all the entries on the list along with the models to download for each.
+    pub fn get_entries(&self) -> Result<Vec<AutoDownloadEntry>, BufkitDataErr> {
+        let mut stmt = self
+            .db_conn
+            .prepare("SELECT list, station_num, models, window_start, window_end FROM download WHERE list = ?1")?;
+
+        let results: Result<Vec<AutoDownloadEntry>, BufkitDataErr> = stmt
+            .query_and_then([&self.list], Self::parse_entry)?
+            .collect();
+
+        results
+    }
+
+    /// Get the entries for a site on every list it is on.
+    pub fn entries_for_site(
+        &self,
+        station_num: StationNumber,
+    ) -> Result<Vec<AutoDownloadEntry>, BufkitDataErr> {
+        let station_num: u32 = station_num.into();
+
+        let mut stmt = self.db_conn.prepare(
+            concat!(
+                "SELECT list, station_num, models, window_start, window_end FROM download",
+                " WHERE station_num = ?1 ORDER BY list"
+            ),
+        )?;
+
+        let results: Result<Vec<AutoDownloadEntry>, BufkitDataErr> = stmt
+            .query_and_then([&station_num], Self::parse_entry)?
+            .collect();
+
+        results
+    }
+
+    fn parse_entry(row: &rusqlite::Row) -> Result<AutoDownloadEntry, BufkitDataErr> {
+        let list: String = row.get(0)?;
+        let station_num: u32 = row.get(1)?;
+        let models: Option<String> = row.get(2)?;
+        let window_start: Option<String> = row.get(3)?;
+        let window_end: Option<String> = row.get(4)?;
+
+        let window = match (window_start, window_end) {
+            (Some(start), Some(end)) => Some(DownloadWindow::parse(&start, &end)?),
+            _ => None,
+        };
+
+        Ok(AutoDownloadEntry {
+            list,
+            station_num: StationNumber::from(station_num),
+            models: models.map(|models| parse_models(&models)).transpose()?,
+            window,
+        })
+    }
+
     pub fn add_site(&self, station_num: StationNumber) -> Result<(), BufkitDataErr> {
         let station_num: u32 = station_num.into();
 
         self.db_conn.execute(
-            "INSERT OR IGNORE INTO download (station_num) VALUES (?1)",
-            &[&station_num],
+            "INSERT OR IGNORE INTO download (list, station_num) VALUES (?1, ?2)",
+            rusqlite::params![self.list, station_num],
         )?;
 
         Ok(())
     }
 
+    /// Set the models to download for a site, adding it to the list if it isn't already there.
+    ///
+    /// If `models` is `None`, all models are downloaded.
+    pub fn set_models(
+        &self,
+        station_num: StationNumber,
+        models: Option<&[Model]>,
+    ) -> Result<(), BufkitDataErr> {
+        let station_num: u32 = station_num.into();
+        let models: Option<String> = models.map(|models| {
+            models
+                .iter()
+                .map(|m| m.as_static_str())
+                .collect::<Vec<_>>()
+                .join(",")
+        });
+
+        self.db_conn.execute(
+            "INSERT INTO download (list, station_num, models) VALUES (?1, ?2, ?3)
+                ON CONFLICT (list, station_num) DO UPDATE SET models = excluded.models",
+            rusqlite::params![self.list, station_num, models],
+        )?;
+
+        Ok(())
+    }
+
+    /// Get the models to download for a site. `None` means all models, or that the site isn't
+    /// automatically downloaded at all.
+    pub fn models_for(
+        &self,
+        station_num: StationNumber,
+    ) -> Result<Option<Vec<Model>>, BufkitDataErr> {
+        let station_num: u32 = station_num.into();
+
+        let models: Option<String> = self
+            .db_conn
+            .query_row(
+                "SELECT models FROM download WHERE list = ?1 AND station_num = ?2",
+                rusqlite::params![self.list, station_num],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+
+        models.map(|models| parse_models(&models)).transpose()
+    }
+
+    /// Set when to download a site, adding it to the list if it isn't already there.
+    ///
+    /// If `window` is `None`, the site is always downloaded.
+    pub fn set_window(
+        &self,
+        station_num: StationNumber,
+        window: Option<DownloadWindow>,
+    ) -> Result<(), BufkitDataErr> {
+        let station_num: u32 = station_num.into();
+        let start = window.map(|w| w.start_string());
+        let end = window.map(|w| w.end_string());
+
+        self.db_conn.execute(
+            "INSERT INTO download (list, station_num, window_start, window_end)
+                VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT (list, station_num) DO UPDATE
+                SET window_start = excluded.window_start, window_end = excluded.window_end",
+            rusqlite::params![self.list, station_num, start, end],
+        )?;
+
+        Ok(())
+    }
+
+    /// Get the entry for a site on the selected list, if it is on the list.
+    pub fn entry_for(
+        &self,
+        station_num: StationNumber,
+    ) -> Result<Option<AutoDownloadEntry>, BufkitDataErr> {
+        let station_num: u32 = station_num.into();
+
+        let mut stmt = self.db_conn.prepare(concat!(
+            "SELECT list, station_num, models, window_start, window_end FROM download",
+            " WHERE list = ?1 AND station_num = ?2"
+        ))?;
+
+        stmt.query_and_then(rusqlite::params![self.list, station_num], Self::parse_entry)?
+            .next()
+            .transpose()
+    }
+
     pub fn remove_site(&self, station_num: StationNumber) -> Result<(), BufkitDataErr> {
         let station_num: u32 = station_num.into();
 
         self.db_conn.execute(
-            "DELETE FROM download WHERE station_num = ?1",
-            &[&station_num],
+            "DELETE FROM download WHERE list = ?1 AND station_num = ?2",
+            rusqlite::params![self.list, station_num],
+        )?;
+
+        Ok(())
+    }
+
+    /// Move a site to a new station number on every list. Where the new station number is
+    /// already on a list its entry is kept and the old one is dropped.
+    pub fn move_site(&self, from: StationNumber, to: StationNumber) -> Result<(), BufkitDataErr> {
+        let from: u32 = from.into();
+        let to: u32 = to.into();
+
+        self.db_conn.execute(
+            "UPDATE OR IGNORE download SET station_num = ?2 WHERE station_num = ?1",
+            [&from, &to],
         )?;
+        self.db_conn
+            .execute("DELETE FROM download WHERE station_num = ?1", [&from])?;
 
         Ok(())
     }
@@ -70,11 +411,151 @@ impl AutoDownloadListDb {
         let station_num: u32 = station_num.into();
 
         let count: u32 = self.db_conn.query_row(
-            "SELECT COUNT(*) FROM download WHERE station_num = ?1",
-            &[&station_num],
+            "SELECT COUNT(*) FROM download WHERE list = ?1 AND station_num = ?2",
+            rusqlite::params![self.list, station_num],
             |row| row.get::<_, u32>(0),
         )?;
 
         Ok(count == 1)
     }
 }
+
+fn parse_models(models: &str) -> Result<Vec<Model>, BufkitDataErr> {
+    models
+        .split(',')
+        .map(|model| Model::from_str(model.trim()).map_err(BufkitDataErr::StrumError))
+        .collect()
+}
+
+#[cfg(test)]
+mod unit {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_migrate_from_unversioned() {
+        let tmp = TempDir::with_prefix("bfkmd-test-auto-download").unwrap();
+
+        // The schema before there was a user_version.
+        {
+            let db_conn = Connection::open(tmp.path().join("auto_download.db")).unwrap();
+            db_conn
+                .execute_batch(
+                    "CREATE TABLE download (station_num INT PRIMARY KEY);
+                    INSERT INTO download (station_num) VALUES (727730), (727755);",
+                )
+                .unwrap();
+        }
+
+        let dl_db = AutoDownloadListDb::open_or_create(tmp.path()).unwrap();
+        let mut stations = dl_db.get_list().unwrap();
+        stations.sort_unstable();
+        assert_eq!(
+            stations,
+            vec![StationNumber::from(727730), StationNumber::from(727755)]
+        );
+
+        let entry = dl_db.entry_for(StationNumber::from(727730)).unwrap().unwrap();
+        assert_eq!(entry.list, AutoDownloadListDb::DEFAULT_LIST);
+        assert!(entry.models.is_none());
+        assert!(entry.window.is_none());
+
+        let version: i32 = dl_db
+            .db_conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, 3);
+
+        // Opening it again doesn't run the migrations twice.
+        drop(dl_db);
+        let dl_db = AutoDownloadListDb::open_or_create(tmp.path()).unwrap();
+        assert_eq!(dl_db.get_list().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_named_lists() {
+        let tmp = TempDir::with_prefix("bfkmd-test-auto-download").unwrap();
+        let stn = StationNumber::from(727730);
+
+        let dl_db = AutoDownloadListDb::open_or_create(tmp.path()).unwrap();
+        dl_db.add_site(stn).unwrap();
+        let fire = dl_db.with_list("fire");
+        fire.set_models(stn, Some(&[Model::NAM4KM])).unwrap();
+
+        assert_eq!(fire.list_names().unwrap(), vec!["default", "fire"]);
+        assert_eq!(fire.models_for(stn).unwrap(), Some(vec![Model::NAM4KM]));
+        assert_eq!(fire.entries_for_site(stn).unwrap().len(), 2);
+
+        let dl_db = fire.with_list(AutoDownloadListDb::DEFAULT_LIST);
+        assert_eq!(dl_db.models_for(stn).unwrap(), None);
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_window_parse() {
+        assert_eq!(
+            DownloadWindow::parse("2024-05-01", "2024-09-30").unwrap(),
+            DownloadWindow::Dates {
+                start: date(2024, 5, 1),
+                end: date(2024, 9, 30)
+            }
+        );
+        assert_eq!(
+            DownloadWindow::parse("11-01", "02-29").unwrap(),
+            DownloadWindow::Season {
+                start: (11, 1),
+                end: (2, 29)
+            }
+        );
+
+        assert!(DownloadWindow::parse("2024-09-30", "2024-05-01").is_err());
+        assert!(DownloadWindow::parse("2024-05-01", "09-30").is_err());
+        assert!(DownloadWindow::parse("13-01", "02-01").is_err());
+        assert!(DownloadWindow::parse("", "").is_err());
+    }
+
+    #[test]
+    fn test_window_is_active() {
+        let dates = DownloadWindow::parse("2024-05-01", "2024-09-30").unwrap();
+        assert!(dates.is_active(date(2024, 5, 1)));
+        assert!(dates.is_active(date(2024, 9, 30)));
+        assert!(!dates.is_active(date(2024, 4, 30)));
+        assert!(!dates.is_active(date(2025, 6, 1)));
+
+        let summer = DownloadWindow::parse("06-01", "09-15").unwrap();
+        assert!(summer.is_active(date(2019, 6, 1)));
+        assert!(summer.is_active(date(2031, 9, 15)));
+        assert!(!summer.is_active(date(2024, 9, 16)));
+
+        // Wraps around the end of the year.
+        let winter = DownloadWindow::parse("11-01", "03-31").unwrap();
+        assert!(winter.is_active(date(2024, 12, 31)));
+        assert!(winter.is_active(date(2025, 1, 1)));
+        assert!(winter.is_active(date(2025, 3, 31)));
+        assert!(!winter.is_active(date(2025, 4, 1)));
+        assert!(!winter.is_active(date(2025, 10, 31)));
+    }
+
+    #[test]
+    fn test_window_round_trip() {
+        let tmp = TempDir::with_prefix("bfkmd-test-auto-download").unwrap();
+        let stn = StationNumber::from(727730);
+        let window = DownloadWindow::parse("11-01", "03-31").unwrap();
+
+        let dl_db = AutoDownloadListDb::open_or_create(tmp.path()).unwrap();
+        dl_db.set_window(stn, Some(window)).unwrap();
+
+        let entry = dl_db.entry_for(stn).unwrap().unwrap();
+        assert_eq!(entry.window, Some(window));
+        assert!(entry.is_active(date(2025, 1, 15)));
+        assert!(!entry.is_active(date(2025, 7, 15)));
+        assert_eq!(window.to_string(), "11-01 to 03-31");
+
+        dl_db.set_window(stn, None).unwrap();
+        assert!(dl_db.entry_for(stn).unwrap().unwrap().is_active(date(2025, 7, 15)));
+    }
+}
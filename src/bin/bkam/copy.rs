@@ -1,13 +1,16 @@
-use bfkmd::parse_date_string;
+use bfkmd::{bail, parse_date_string};
 use bufkit_data::{Archive, Model, StationNumber};
 use chrono::{NaiveDate, Utc};
 use clap::ArgMatches;
 use std::str::FromStr;
-use std::{error::Error, path::PathBuf};
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
 use strum::IntoEnumIterator;
 
-pub fn copy(root: &PathBuf, sub_args: &ArgMatches) -> Result<(), Box<dyn Error>> {
-    let arch = Archive::connect(root)?;
+pub fn copy(root: &Path, sub_args: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let arch = Archive::connect(&root)?;
 
     let models = {
         let mut models: Vec<Model> = sub_args
@@ -26,12 +29,12 @@ pub fn copy(root: &PathBuf, sub_args: &ArgMatches) -> Result<(), Box<dyn Error>>
 
     let start = sub_args
         .value_of("start")
-        .map(|after_str| parse_date_string(after_str))
+        .map(parse_date_string)
         .unwrap_or_else(|| NaiveDate::from_ymd_opt(1900, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap());
 
     let end = sub_args
         .value_of("end")
-        .map(|before_str| parse_date_string(before_str))
+        .map(parse_date_string)
         .unwrap_or_else(|| Utc::now().naive_utc());
 
     let sites: Vec<String> = sub_args
@@ -40,6 +43,16 @@ pub fn copy(root: &PathBuf, sub_args: &ArgMatches) -> Result<(), Box<dyn Error>>
         .flat_map(|site_iter| site_iter.map(ToOwned::to_owned))
         .collect();
 
+    for site in &sites {
+        if Model::iter().all(|model| arch.station_num_for_id(site, model).is_err()) {
+            bail(&format!(
+                "Site {} does not exist in the archive!{}",
+                site,
+                bfkmd::did_you_mean(&arch, site)
+            ));
+        }
+    }
+
     let mut stations: Vec<StationNumber> = vec![];
     for &model in &models {
         if sites.is_empty() {
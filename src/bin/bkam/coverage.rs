@@ -0,0 +1,293 @@
+use bfkmd::{OutputFormat, TablePrinter, add_months, bail};
+use bufkit_data::{Archive, BufkitDataErr, Model, StateProv, StationNumber};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Utc};
+use clap::ArgMatches;
+use serde::Serialize;
+use std::{collections::HashSet, error::Error, path::Path, str::FromStr};
+
+/// The number of months to show when no start is given.
+const DEFAULT_MONTHS: u32 = 12;
+
+/// Coverage for one site, model, and month.
+#[derive(Debug, Serialize)]
+struct CoverageRecord {
+    station_num: u32,
+    ids: String,
+    name: Option<String>,
+    model: &'static str,
+    month: String,
+    expected: usize,
+    present: usize,
+    /// `None` when no runs were expected, e.g. the month is in the future or before the first run
+    /// in the archive.
+    percent: Option<f64>,
+}
+
+pub fn coverage(root: &Path, sub_args: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let arch = Archive::connect(&root)?;
+
+    //
+    // Filters
+    //
+    let sites: HashSet<StationNumber> = sub_args
+        .values_of("sites")
+        .into_iter()
+        .flatten()
+        .map(|site| {
+            site.parse::<u32>()
+                .map(StationNumber::from)
+                .or_else(|_| bfkmd::site_id_to_station_num(&arch, site))
+                .unwrap_or_else(|_| bail(&format!("Unknown site: {}", site)))
+        })
+        .collect();
+
+    let models: Vec<Model> = sub_args
+        .values_of("models")
+        .into_iter()
+        .flatten()
+        .map(|model| {
+            Model::from_str(model)
+                .unwrap_or_else(|_| bail(&format!("Model {} does not exist!", model)))
+        })
+        .collect();
+
+    let state = sub_args.value_of("state").map(|state| {
+        StateProv::from_str(&state.to_uppercase())
+            .unwrap_or_else(|_| bail(&format!("Unable to parse state/providence: {}", state)))
+    });
+
+    //
+    // The months to check
+    //
+    let now = Utc::now().naive_utc();
+    let end_month = sub_args
+        .value_of("end")
+        .map(parse_month)
+        .unwrap_or_else(|| now.date().with_day(1).unwrap());
+    let start_month = sub_args
+        .value_of("start")
+        .map(parse_month)
+        .unwrap_or_else(|| add_months(end_month, 1 - DEFAULT_MONTHS as i32));
+    if start_month > end_month {
+        bail("The start month is after the end month.");
+    }
+
+    let mut months = vec![start_month];
+    while let Some(&last) = months.last()
+        && last < end_month
+    {
+        months.push(add_months(last, 1));
+    }
+
+    // Safe to unwrap because the argument has a default value.
+    let format = OutputFormat::from_str(sub_args.value_of("format").unwrap())?;
+
+    let mut summaries = arch.station_summaries()?;
+    summaries.retain(|site| sites.is_empty() || sites.contains(&site.station_num));
+    summaries.retain(|site| state.is_none() || site.state == state);
+    summaries.sort_unstable_by_key(|site| Into::<u32>::into(site.station_num));
+
+    //
+    // Calculate the coverage
+    //
+    let mut records: Vec<CoverageRecord> = vec![];
+    for site in &summaries {
+        let mut site_models = site.models.clone();
+        site_models.sort_unstable();
+        site_models.dedup();
+        site_models.retain(|model| models.is_empty() || models.contains(model));
+
+        for model in site_models {
+            let inventory = match arch.inventory(site.station_num, model) {
+                Ok(inventory) => inventory,
+                Err(BufkitDataErr::NotEnoughData) => vec![],
+                Err(err) => return Err(err.into()),
+            };
+
+            for &month in &months {
+                let (expected, present) = month_coverage(model, &inventory, month, now);
+
+                records.push(CoverageRecord {
+                    station_num: site.station_num.into(),
+                    ids: site.ids_as_string(),
+                    name: site.name.clone(),
+                    model: model.as_static_str(),
+                    month: month.format("%Y-%m").to_string(),
+                    expected,
+                    present,
+                    percent: if expected > 0 {
+                        Some(100.0 * present as f64 / expected as f64)
+                    } else {
+                        None
+                    },
+                });
+            }
+        }
+    }
+
+    match format {
+        OutputFormat::Json => return OutputFormat::write_json(&records),
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let rows: Vec<Vec<String>> = records
+                .into_iter()
+                .map(|rec| {
+                    vec![
+                        rec.station_num.to_string(),
+                        rec.ids,
+                        rec.name.unwrap_or_default(),
+                        rec.model.to_owned(),
+                        rec.month,
+                        rec.expected.to_string(),
+                        rec.present.to_string(),
+                        rec.percent.map(|pct| format!("{:.1}", pct)).unwrap_or_default(),
+                    ]
+                })
+                .collect();
+
+            let header = [
+                "station_num",
+                "ids",
+                "name",
+                "model",
+                "month",
+                "expected",
+                "present",
+                "percent",
+            ];
+            return format.write_delimited(&header, &rows);
+        }
+        OutputFormat::Table => {}
+    }
+
+    if records.is_empty() {
+        println!("No sites matched criteria.");
+        return Ok(());
+    }
+
+    //
+    // Heat map, one row per site and model with a column for each month.
+    //
+    let mut tp = TablePrinter::new()
+        .with_title("Archive Coverage".to_owned())
+        .with_header(format!(
+            "Percent of expected runs present, {} to {}",
+            start_month.format("%Y-%m"),
+            end_month.format("%Y-%m")
+        ))
+        .with_footer("█ 100%  ▓ 90% or more  ▒ 50% or more  ░ less than 50%".to_owned())
+        .with_column::<&str, String>("Stn Num", &[])
+        .with_column::<&str, String>("IDs", &[])
+        .with_column::<&str, String>("Model", &[]);
+    for month in &months {
+        tp = tp.with_column::<String, String>(month.format("%Y-%m").to_string(), &[]);
+    }
+
+    for site_model in records.chunks(months.len()) {
+        let first = &site_model[0];
+        let mut row = vec![
+            first.station_num.to_string(),
+            first.ids.clone(),
+            first.model.to_uppercase(),
+        ];
+        row.extend(site_model.iter().map(|rec| heat_map_cell(rec.percent)));
+        tp.add_row(row);
+    }
+
+    tp.print()?;
+
+    Ok(())
+}
+
+/// Count the expected and present runs in a month.
+///
+/// Runs after `now` aren't expected yet, and neither are runs before the first one in the
+/// inventory, since the site wasn't being archived then.
+fn month_coverage(
+    model: Model,
+    inventory: &[NaiveDateTime],
+    month: NaiveDate,
+    now: NaiveDateTime,
+) -> (usize, usize) {
+    let first_run = match inventory.first() {
+        Some(&first_run) => first_run,
+        None => return (0, 0),
+    };
+
+    let start = month.and_hms_opt(0, 0, 0).unwrap().max(first_run);
+    let end = add_months(month, 1).and_hms_opt(0, 0, 0).unwrap() - Duration::hours(1);
+    let end = end.min(now);
+
+    if end < start {
+        return (0, 0);
+    }
+
+    let expected = model.all_runs(&start, &end).count();
+
+    // The inventory is sorted.
+    let present = inventory.partition_point(|&run| run <= end)
+        - inventory.partition_point(|&run| run < start);
+
+    (expected, present)
+}
+
+fn heat_map_cell(percent: Option<f64>) -> String {
+    match percent {
+        None => "-".to_owned(),
+        Some(pct) => {
+            let shade = if pct >= 100.0 {
+                '█'
+            } else if pct >= 90.0 {
+                '▓'
+            } else if pct >= 50.0 {
+                '▒'
+            } else if pct > 0.0 {
+                '░'
+            } else {
+                ' '
+            };
+
+            format!("{} {:3.0}", shade, pct.floor())
+        }
+    }
+}
+
+/// Parse YYYY-MM into the first day of the month.
+fn parse_month(month: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+        .unwrap_or_else(|_| bail(&format!("Could not parse month, use YYYY-MM: {}", month)))
+}
+
+#[cfg(test)]
+mod unit {
+    use super::*;
+
+    fn time(month: u32, day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, month, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_month_coverage() {
+        let may = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let later = time(12, 1, 0);
+
+        // The GFS runs every 6 hours, 124 runs in May. The inventory starts on May 1.
+        let inventory = vec![time(5, 1, 0), time(5, 1, 6), time(5, 2, 0), time(6, 1, 0)];
+        assert_eq!(month_coverage(Model::GFS, &inventory, may, later), (124, 3));
+
+        // Partway through the month only the runs so far are expected.
+        assert_eq!(month_coverage(Model::GFS, &inventory, may, time(5, 2, 0)), (5, 3));
+
+        // Nothing is expected before the first run in the archive, or in the future.
+        let april = NaiveDate::from_ymd_opt(2024, 4, 1).unwrap();
+        assert_eq!(month_coverage(Model::GFS, &inventory, april, later), (0, 0));
+        assert_eq!(month_coverage(Model::GFS, &inventory, may, time(4, 15, 0)), (0, 0));
+        assert_eq!(month_coverage(Model::GFS, &[], may, later), (0, 0));
+
+        // A site added partway through the month is only expected from its first run.
+        let inventory = vec![time(5, 31, 0), time(5, 31, 12), time(5, 31, 18)];
+        assert_eq!(month_coverage(Model::GFS, &inventory, may, later), (4, 3));
+    }
+}
@@ -1,9 +1,12 @@
 use bufkit_data::Archive;
 use clap::ArgMatches;
 use dirs::home_dir;
-use std::{error::Error, path::PathBuf};
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
 
-pub fn create(_root: &PathBuf, sub_args: &ArgMatches) -> Result<(), Box<dyn Error>> {
+pub fn create(_root: &Path, sub_args: &ArgMatches) -> Result<(), Box<dyn Error>> {
     let root = &sub_args
         .value_of("archive_root")
         .map(PathBuf::from)
@@ -11,7 +14,7 @@ pub fn create(_root: &PathBuf, sub_args: &ArgMatches) -> Result<(), Box<dyn Erro
         .expect("Invalid root.");
 
     // Check if the archive already exists. (try connecting to it)
-    let already_exists: bool = Archive::connect(root).is_ok();
+    let already_exists: bool = Archive::connect(&root).is_ok();
 
     if already_exists && sub_args.is_present("force") {
         ::std::fs::remove_dir_all(root)?;
@@ -19,7 +22,7 @@ pub fn create(_root: &PathBuf, sub_args: &ArgMatches) -> Result<(), Box<dyn Erro
         return Err("Archive already exists, must use --force to overwrite.".into());
     }
 
-    Archive::create(root)?;
+    Archive::create(&root)?;
 
     Ok(())
 }
@@ -1,119 +1,648 @@
-use bfkmd::{bail, parse_date_string};
-use bufkit_data::{Archive, BufkitDataErr, Model, StationNumber};
-use chrono::NaiveDateTime;
+use bfkmd::{ArchiveIndex, AutoDownloadListDb, bail, parse_date_string};
+use bufkit_data::{Archive, Model, StateProv, StationNumber};
+use chrono::{
+    Datelike, NaiveDateTime, Timelike, Utc,
+    format::StrftimeItems,
+};
 use clap::ArgMatches;
+use flate2::{Compression, write::GzEncoder};
 use std::{
+    collections::HashSet,
     error::Error,
-    fs::File,
-    io::{BufWriter, Write},
-    path::{Path, PathBuf},
+    fmt::Write as _,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Component, Path, PathBuf},
     str::FromStr,
 };
+use strum::IntoEnumIterator;
+use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
 
-pub fn export(root: &PathBuf, sub_args: &ArgMatches) -> Result<(), Box<dyn Error>> {
-    let arch = Archive::connect(root)?;
-
-    // unwrap is ok, these are required.
-    let site_id = sub_args.value_of("site").unwrap();
-    let model = sub_args.value_of("model").unwrap();
-    let target = sub_args.value_of("target").unwrap();
+pub fn export(root: &Path, sub_args: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let arch = Archive::connect(&root)?;
+    let index = ArchiveIndex::open(root)?;
 
     //
-    // Validate required arguments.
+    // The positional arguments are the target, or a site, model, and target. A bundle replaces
+    // the target.
     //
-    let model = match Model::from_str(model) {
-        Ok(model) => model,
-        Err(_) => {
-            bail(&format!("Model {} does not exist in the archive!", model));
-        }
-    };
+    let args: Vec<&str> = sub_args.values_of("args").into_iter().flatten().collect();
+    let bundle = sub_args.value_of("bundle");
 
-    let site: StationNumber = match arch.station_num_for_id(site_id, model) {
-        Ok(station_num) => station_num,
-        Err(BufkitDataErr::NotInIndex) => {
-            bail(&format!("Site {} does not exist in the archive!", site_id))
+    // Keep errors out of the data when the run is written to stdout.
+    if bundle.is_none() && args.last() == Some(&"-") {
+        bfkmd::errors_to_stderr();
+    }
+    let (site_list, model_list, target) = match (args.as_slice(), bundle) {
+        ([target], None) => (None, None, Some(*target)),
+        ([site, model, target], None) => (Some(*site), Some(*model), Some(*target)),
+        ([], Some(_)) => (None, None, None),
+        ([site, model], Some(_)) => (Some(*site), Some(*model), None),
+        (_, None) => {
+            bail("Give the target directory, or a site, a model, and the target directory.")
+        }
+        (_, Some(_)) => {
+            bail("A bundle replaces the target directory, give only a site and a model.")
         }
-        Err(err) => bail(&format!("Database error: {}", err)),
     };
 
-    let target = Path::new(target);
-    if !target.is_dir() {
+    //
+    // Validate the arguments.
+    //
+    let models = parse_models(model_list, sub_args);
+    let sites = select_sites(&arch, site_list, &models, sub_args)?;
+    if sites.is_empty() {
+        bail("No sites selected, use --sites, --state, or --auto-download.");
+    }
+
+    let target = target.filter(|&target| target != "-").map(Path::new);
+    if let Some(target) = target
+        && !target.is_dir()
+    {
         bail(&format!(
             "Path {} is not a directory that already exists.",
             target.display()
         ));
     }
 
+    let bundle_format = bundle.map(|bundle| {
+        BundleFormat::from_path(bundle).unwrap_or_else(|| {
+            bail(&format!(
+                "Unknown bundle format {}, use a .tar.gz, .tgz, or .zip file.",
+                bundle
+            ))
+        })
+    });
+
+    let layout = sub_args.value_of("layout").unwrap_or("");
+    match fill_template(layout, &TemplateValues::example()) {
+        Ok(dir) if !is_relative_path(&dir) => bail(&format!(
+            "Invalid layout {}: it must stay inside the target, without a leading / or any '..'.",
+            layout
+        )),
+        Ok(_) => {}
+        Err(err) => bail(&format!("Invalid layout {}: {}.", layout, err)),
+    }
+
+    let name_template = sub_args.value_of("name-template");
+    if let Some(name_template) = name_template {
+        match fill_template(name_template, &TemplateValues::example()) {
+            Ok(name) if name.is_empty() || name.contains(['/', '\\']) => bail(&format!(
+                "Invalid name template {}: use --layout for directories.",
+                name_template
+            )),
+            Ok(_) => {}
+            Err(err) => bail(&format!("Invalid name template {}: {}.", name_template, err)),
+        }
+    }
+
     //
     //  Set up optional arguments.
     //
-    #[derive(Debug, Clone, Copy)]
-    enum OptionalDateArg {
-        NotSpecified,
-        Specified(NaiveDateTime),
-    }
+    let start_date = sub_args.value_of("start").map(parse_date_string);
+    let end_date = sub_args.value_of("end").map(parse_date_string).or(start_date);
 
-    let start_date: OptionalDateArg = match sub_args.value_of("start") {
-        Some(start_date) => OptionalDateArg::Specified(parse_date_string(start_date)),
-        None => OptionalDateArg::NotSpecified,
+    // Without a date the most recent run is saved without a date in the file name.
+    let name_template = match name_template {
+        Some(name_template) => name_template,
+        None if sub_args.is_present("no-prefix-date") || start_date.is_none() => {
+            "{site}_{model}.buf"
+        }
+        None => "{init}_{model}_{site}.buf",
     };
 
-    let end_date: OptionalDateArg = match sub_args.value_of("end") {
-        Some(end_date) => OptionalDateArg::Specified(parse_date_string(end_date)),
-        None => start_date,
-    };
+    //
+    // Find the runs for every site and model, and where to save them.
+    //
+    let mut items: Vec<ExportItem> = vec![];
+    let mut paths: HashSet<String> = HashSet::new();
+    for &station_num in &sites {
+        let site = arch.site(station_num);
+        let runs = index.runs_for(station_num)?;
 
-    let no_prefix_date = sub_args.is_present("no-prefix-date");
+        for &model in &models {
+            let model_runs = runs
+                .iter()
+                .filter(|(run_model, _)| *run_model == model)
+                .map(|&(_, init_time)| init_time);
 
-    match (start_date, end_date) {
-        (OptionalDateArg::NotSpecified, OptionalDateArg::NotSpecified) => {
-            let data = arch.retrieve_most_recent(site, model)?;
-            save_file(&target, site_id, model, None, no_prefix_date, &data)?;
-        }
-        (OptionalDateArg::Specified(start), OptionalDateArg::Specified(end)) => {
-            for init_time in model.all_runs(&start, &end) {
-                let data = arch.retrieve(site, model, init_time)?;
-                save_file(
-                    &target,
-                    site_id,
+            let init_times: Vec<NaiveDateTime> = match (start_date, end_date) {
+                (Some(start), Some(end)) => model_runs
+                    .filter(|init_time| *init_time >= start && *init_time <= end)
+                    .collect(),
+                _ => model_runs.max().into_iter().collect(),
+            };
+
+            if init_times.is_empty() {
+                continue;
+            }
+
+            let site_id = arch
+                .most_recent_id(station_num, model)?
+                .unwrap_or_else(|| station_num.to_string());
+
+            for init_time in init_times {
+                let values = TemplateValues {
+                    model,
+                    site_id: &site_id,
+                    station_num,
+                    state: site.as_ref().and_then(|site| site.state),
+                    init_time,
+                };
+                // Safe to unwrap because the templates were checked above.
+                let dir = fill_template(layout, &values).unwrap();
+                let name = fill_template(name_template, &values).unwrap();
+                let path = match dir.trim_end_matches('/') {
+                    "" => name,
+                    dir => format!("{}/{}", dir, name),
+                };
+                if !is_relative_path(&path) {
+                    bail(&format!("Unable to save a run as {}, it is outside the target.", path));
+                }
+
+                if !paths.insert(path.clone()) {
+                    bail(&format!(
+                        "More than one run would be saved as {}, add {{init}} to the name \
+                         template.",
+                        path
+                    ));
+                }
+
+                items.push(ExportItem {
+                    station_num,
                     model,
-                    Some(init_time),
-                    no_prefix_date,
-                    &data,
-                )?;
+                    init_time,
+                    path,
+                });
+            }
+        }
+    }
+
+    if items.is_empty() {
+        bail("No data found for the selected sites, models, and dates.");
+    }
+
+    //
+    // Save them.
+    //
+    let mut sink = match (bundle, bundle_format, target) {
+        (Some(bundle), Some(format), _) => ExportSink::create_bundle(Path::new(bundle), format)?,
+        (_, _, Some(target)) => ExportSink::Directory(target.to_owned()),
+        (_, _, None) => {
+            if items.len() > 1 {
+                bail(&format!(
+                    "Only one run can be written to stdout, but {} were selected. Use --bundle \
+                     for more than one.",
+                    items.len()
+                ));
             }
+            ExportSink::Stdout
         }
-        _ => unreachable!(),
+    };
+
+    for item in &items {
+        let data = arch.retrieve(item.station_num, item.model, item.init_time)?;
+        sink.add(&item.path, &data)?;
+    }
+    sink.finish()?;
+
+    match (bundle, target) {
+        (Some(bundle), _) => println!("Exported {} files to {}.", items.len(), bundle),
+        (None, Some(target)) if sites.len() > 1 || models.len() > 1 => {
+            println!("Exported {} files to {}.", items.len(), target.display())
+        }
+        _ => {}
     }
 
     Ok(())
 }
 
-fn save_file(
-    save_dir: &Path,
-    site_id: &str,
+/// Whether a path stays inside the directory it is relative to, paths in bundles use '/' on every
+/// platform so '\' isn't allowed either.
+fn is_relative_path(path: &str) -> bool {
+    !path.contains('\\')
+        && Path::new(path)
+            .components()
+            .all(|comp| matches!(comp, Component::Normal(_) | Component::CurDir))
+}
+
+/// A model run to export and its path relative to the target directory or in the bundle.
+struct ExportItem {
+    station_num: StationNumber,
     model: Model,
-    init_time: Option<NaiveDateTime>,
-    no_prefix_date: bool,
-    data: &str,
-) -> Result<(), Box<dyn Error>> {
-    let fname: String = match (no_prefix_date, init_time) {
-        (false, Some(init_time)) => {
-            let file_string = init_time.format("%Y%m%d%HZ").to_string();
-
-            format!(
-                "{}_{}_{}.buf",
-                file_string,
-                model.as_static_str(),
-                site_id.to_uppercase()
-            )
-        }
-        _ => format!("{}_{}.buf", site_id.to_uppercase(), model.as_static_str(),),
-    };
+    init_time: NaiveDateTime,
+    path: String,
+}
 
-    let save_path = save_dir.join(fname);
+#[derive(Debug, Clone, Copy)]
+enum BundleFormat {
+    TarGz,
+    Zip,
+}
+
+impl BundleFormat {
+    fn from_path(path: &str) -> Option<Self> {
+        let path = path.to_lowercase();
+
+        if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+            Some(BundleFormat::TarGz)
+        } else if path.ends_with(".zip") {
+            Some(BundleFormat::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// Where the exported files go.
+enum ExportSink {
+    Directory(PathBuf),
+    Stdout,
+    TarGz(tar::Builder<GzEncoder<File>>, PartialFile),
+    Zip(ZipWriter<File>, PartialFile),
+}
+
+/// A bundle is written to a temporary file next to it, and only renamed once it is complete so a
+/// failed export doesn't leave a truncated bundle behind. The temporary file is removed if the
+/// bundle isn't kept.
+struct PartialFile {
+    partial: PathBuf,
+    path: PathBuf,
+    kept: bool,
+}
+
+impl PartialFile {
+    fn new(path: &Path) -> Self {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+
+        PartialFile {
+            partial: path.with_file_name(format!(".{}.partial", file_name)),
+            path: path.to_owned(),
+            kept: false,
+        }
+    }
+
+    fn keep(mut self) -> io::Result<()> {
+        fs::rename(&self.partial, &self.path)?;
+        self.kept = true;
+        Ok(())
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        if !self.kept {
+            let _ = fs::remove_file(&self.partial);
+        }
+    }
+}
+
+impl ExportSink {
+    fn create_bundle(path: &Path, format: BundleFormat) -> Result<Self, Box<dyn Error>> {
+        let partial = PartialFile::new(path);
+        let file = File::create(&partial.partial)?;
+
+        Ok(match format {
+            BundleFormat::TarGz => ExportSink::TarGz(
+                tar::Builder::new(GzEncoder::new(file, Compression::default())),
+                partial,
+            ),
+            BundleFormat::Zip => ExportSink::Zip(ZipWriter::new(file), partial),
+        })
+    }
+
+    fn add(&mut self, path: &str, data: &str) -> Result<(), Box<dyn Error>> {
+        match self {
+            ExportSink::Directory(target) => {
+                let save_path = target.join(path);
+                if let Some(dir) = save_path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                save_file(&save_path, data)?;
+            }
+            ExportSink::Stdout => {
+                let stdout = io::stdout();
+                let mut lock = stdout.lock();
+                // A closed pipe, e.g. piping into head, is not an error.
+                match lock.write_all(data.as_bytes()).and_then(|_| lock.flush()) {
+                    Err(err) if err.kind() != io::ErrorKind::BrokenPipe => return Err(err.into()),
+                    _ => {}
+                }
+            }
+            ExportSink::TarGz(builder, _) => {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(data.len() as u64);
+                header.set_mode(0o644);
+                header.set_mtime(Utc::now().timestamp().max(0) as u64);
+                builder.append_data(&mut header, path, data.as_bytes())?;
+            }
+            ExportSink::Zip(writer, _) => {
+                let options =
+                    SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+                writer.start_file(path, options)?;
+                writer.write_all(data.as_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), Box<dyn Error>> {
+        match self {
+            ExportSink::Directory(_) | ExportSink::Stdout => {}
+            ExportSink::TarGz(builder, partial) => {
+                // Close the file before renaming it.
+                drop(builder.into_inner()?.finish()?);
+                partial.keep()?;
+            }
+            ExportSink::Zip(writer, partial) => {
+                drop(writer.finish()?);
+                partial.keep()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Get the models from the comma separated list of the legacy positional argument and the
+/// --models option, all of them if neither is given.
+fn parse_models(model_list: Option<&str>, sub_args: &ArgMatches) -> Vec<Model> {
+    let model_strs: Vec<&str> = model_list
+        .into_iter()
+        .flat_map(|list| list.split(','))
+        .chain(sub_args.values_of("models").into_iter().flatten())
+        .map(str::trim)
+        .filter(|model| !model.is_empty())
+        .collect();
+
+    if model_strs.is_empty() || model_strs.iter().any(|m| m.eq_ignore_ascii_case("all")) {
+        return Model::iter().collect();
+    }
+
+    let mut models: Vec<Model> = model_strs
+        .into_iter()
+        .map(|model| match Model::from_str(model) {
+            Ok(model) => model,
+            Err(_) => bail(&format!("Model {} does not exist in the archive!", model)),
+        })
+        .collect();
+    models.sort_unstable();
+    models.dedup();
+
+    models
+}
+
+/// Get the sites from the comma separated list of the legacy positional argument, --sites,
+/// --state, and --auto-download.
+///
+/// Ids are looked up for the models being exported first, so an id used by different stations in
+/// other models still finds the right one.
+fn select_sites(
+    arch: &Archive,
+    site_list: Option<&str>,
+    models: &[Model],
+    sub_args: &ArgMatches,
+) -> Result<Vec<StationNumber>, Box<dyn Error>> {
+    let mut sites: Vec<StationNumber> = vec![];
+
+    let site_strs = site_list
+        .into_iter()
+        .flat_map(|list| list.split(','))
+        .chain(sub_args.values_of("sites").into_iter().flatten())
+        .map(str::trim)
+        .filter(|site| !site.is_empty());
+    for site in site_strs {
+        let station_num = match site.parse::<u32>() {
+            Ok(stn_num) if arch.site(StationNumber::from(stn_num)).is_some() => {
+                StationNumber::from(stn_num)
+            }
+            _ => {
+                let mut for_models: Vec<StationNumber> = models
+                    .iter()
+                    .filter_map(|&model| arch.station_num_for_id(site, model).ok())
+                    .collect();
+                for_models.sort_unstable_by_key(|&stn| Into::<u32>::into(stn));
+                for_models.dedup();
+
+                match for_models.as_slice() {
+                    [station_num] => *station_num,
+                    [] => match bfkmd::site_id_to_station_num(arch, site) {
+                        Ok(station_num) => station_num,
+                        Err(err) => {
+                            bail(&format!("Site {} does not exist in the archive: {}", site, err))
+                        }
+                    },
+                    _ => bail(&format!(
+                        "The id {} is used by more than one site for these models, use a station \
+                         number.",
+                        site
+                    )),
+                }
+            }
+        };
+        sites.push(station_num);
+    }
+
+    let states: Vec<StateProv> = sub_args
+        .values_of("state")
+        .into_iter()
+        .flatten()
+        .map(|st| {
+            StateProv::from_str(&st.to_uppercase())
+                .unwrap_or_else(|_| bail(&format!("Unable to parse state/providence: {}", st)))
+        })
+        .collect();
+    if !states.is_empty() {
+        arch.sites()?
+            .into_iter()
+            .filter(|site| site.state.is_some_and(|st| states.contains(&st)))
+            .for_each(|site| sites.push(site.station_num));
+    }
+
+    if sub_args.is_present("auto-download") {
+        let mut dl_db = AutoDownloadListDb::open_or_create(arch.root())?;
+        if let Some(list) = sub_args.value_of("list") {
+            dl_db = dl_db.with_list(list);
+        }
+        sites.extend(dl_db.get_list()?);
+    }
+
+    sites.sort_unstable_by_key(|&stn| Into::<u32>::into(stn));
+    sites.dedup();
+
+    Ok(sites)
+}
+
+/// The values for the placeholders in the output layout and file names.
+struct TemplateValues<'a> {
+    model: Model,
+    site_id: &'a str,
+    station_num: StationNumber,
+    state: Option<StateProv>,
+    init_time: NaiveDateTime,
+}
+
+impl TemplateValues<'static> {
+    /// Values for checking a template before exporting anything.
+    fn example() -> Self {
+        TemplateValues {
+            model: Model::GFS,
+            site_id: "KMSO",
+            station_num: StationNumber::from(727730),
+            state: Some(StateProv::MT),
+            init_time: NaiveDateTime::default(),
+        }
+    }
+}
+
+/// Replace the placeholders like {model} in a template, or describe what is wrong with it.
+///
+/// The init time takes a strftime format, e.g. {init:%Y%m%d%H}, and defaults to YYYYMMDDHHZ.
+fn fill_template(template: &str, values: &TemplateValues) -> Result<String, String> {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(open) = rest.find('{') {
+        filled.push_str(&rest[..open]);
+
+        let close = match rest[open..].find('}') {
+            Some(close) => open + close,
+            None => return Err(format!("unclosed placeholder {}", &rest[open..])),
+        };
+
+        let placeholder = &rest[open + 1..close];
+        match placeholder.split_once(':') {
+            Some(("init", fmt)) => filled.push_str(&format_init_time(values.init_time, fmt)?),
+            Some(_) => return Err(format!("unknown placeholder {{{}}}", placeholder)),
+            None => match placeholder {
+                "model" => filled.push_str(values.model.as_static_str()),
+                "site" => filled.push_str(&values.site_id.to_uppercase()),
+                "stn" => filled.push_str(&values.station_num.to_string()),
+                "state" => {
+                    let state = values.state.map(|st| st.as_static_str()).unwrap_or("unknown");
+                    filled.push_str(state);
+                }
+                "init" => filled.push_str(&format_init_time(values.init_time, "%Y%m%d%HZ")?),
+                "year" => filled.push_str(&values.init_time.year().to_string()),
+                "month" => filled.push_str(&format!("{:02}", values.init_time.month())),
+                "cycle" => filled.push_str(&format!("{:02}", values.init_time.hour())),
+                _ => return Err(format!("unknown placeholder {{{}}}", placeholder)),
+            },
+        }
+
+        rest = &rest[close + 1..];
+    }
+    filled.push_str(rest);
+
+    Ok(filled)
+}
+
+/// Format an init time without panicking on a bad format.
+///
+/// Chrono panics in `to_string` when a format is invalid or needs a time zone (e.g. %Z), which
+/// an init time doesn't have, so write it out and report the error instead.
+fn format_init_time(init_time: NaiveDateTime, fmt: &str) -> Result<String, String> {
+    let mut formatted = String::new();
+    write!(formatted, "{}", init_time.format_with_items(StrftimeItems::new(fmt)))
+        .map_err(|_| format!("invalid time format {}", fmt))?;
+
+    Ok(formatted)
+}
+
+fn save_file(save_path: &Path, data: &str) -> Result<(), Box<dyn Error>> {
     let f = File::create(save_path)?;
     let mut bw = BufWriter::new(f);
     bw.write_all(data.as_bytes())?;
     Ok(())
 }
+
+#[cfg(test)]
+mod unit {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn values() -> TemplateValues<'static> {
+        TemplateValues {
+            init_time: NaiveDate::from_ymd_opt(2020, 5, 17)
+                .unwrap()
+                .and_hms_opt(6, 0, 0)
+                .unwrap(),
+            ..TemplateValues::example()
+        }
+    }
+
+    #[test]
+    fn test_fill_template() {
+        let values = values();
+
+        assert_eq!(
+            fill_template("{model}/{site}_{stn}_{state}", &values).unwrap(),
+            "gfs/KMSO_727730_MT"
+        );
+        assert_eq!(fill_template("{init}.buf", &values).unwrap(), "2020051706Z.buf");
+        assert_eq!(
+            fill_template("{year}/{month}/{cycle}", &values).unwrap(),
+            "2020/05/06"
+        );
+        assert_eq!(fill_template("{init:%Y-%m-%d_%H}", &values).unwrap(), "2020-05-17_06");
+        assert_eq!(fill_template("no placeholders", &values).unwrap(), "no placeholders");
+
+        let no_state = TemplateValues { state: None, ..values };
+        assert_eq!(fill_template("{state}", &no_state).unwrap(), "unknown");
+    }
+
+    #[test]
+    fn test_is_relative_path() {
+        assert!(is_relative_path("gfs/KMSO/2020051706Z.buf"));
+        assert!(is_relative_path("./gfs_kmso.buf"));
+        assert!(is_relative_path("kmso.buf"));
+
+        assert!(!is_relative_path("/tmp/kmso.buf"));
+        assert!(!is_relative_path("../kmso.buf"));
+        assert!(!is_relative_path("gfs/../../kmso.buf"));
+        assert!(!is_relative_path("gfs\\..\\kmso.buf"));
+    }
+
+    #[test]
+    fn test_bundle_format_from_path() {
+        assert!(matches!(BundleFormat::from_path("out.tar.gz"), Some(BundleFormat::TarGz)));
+        assert!(matches!(BundleFormat::from_path("out.TGZ"), Some(BundleFormat::TarGz)));
+        assert!(matches!(BundleFormat::from_path("/tmp/out.zip"), Some(BundleFormat::Zip)));
+        assert!(BundleFormat::from_path("out.tar").is_none());
+        assert!(BundleFormat::from_path("out").is_none());
+    }
+
+    #[test]
+    fn test_partial_file() {
+        let tmp = tempfile::TempDir::with_prefix("bfkmd-test-export").unwrap();
+        let path = tmp.path().join("sites.zip");
+
+        // Removed unless it is kept.
+        let partial = PartialFile::new(&path);
+        fs::write(&partial.partial, "partial").unwrap();
+        drop(partial);
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 0);
+
+        let partial = PartialFile::new(&path);
+        fs::write(&partial.partial, "done").unwrap();
+        partial.keep().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "done");
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_fill_template_errors() {
+        let values = values();
+
+        assert!(fill_template("{model", &values).is_err());
+        assert!(fill_template("{bogus}", &values).is_err());
+        assert!(fill_template("{site:%Y}", &values).is_err());
+        assert!(fill_template("{init:%Q}", &values).is_err());
+
+        // Formats that need a time zone can't be filled from an init time.
+        assert!(fill_template("{init:%Y%m%d%H%Z}", &values).is_err());
+        assert!(fill_template("{init:%z}", &values).is_err());
+        assert!(fill_template("{init:%:z}", &values).is_err());
+    }
+}
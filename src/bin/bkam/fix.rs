@@ -1,11 +1,11 @@
 use bufkit_data::Archive;
 use clap::ArgMatches;
-use std::{error::Error, path::PathBuf};
+use std::{error::Error, path::Path};
 
-pub fn fix(root: &PathBuf, _sub_args: &ArgMatches) -> Result<(), Box<dyn Error>> {
+pub fn fix(root: &Path, _sub_args: &ArgMatches) -> Result<(), Box<dyn Error>> {
     // Check that the root exists.
     println!("Checking if the archive location exists.");
-    if !root.as_path().is_dir() {
+    if !root.is_dir() {
         println!("Archive root directory not found. Quitting.");
         return Err("Invalid root.".into());
     } else {
@@ -24,7 +24,7 @@ pub fn fix(root: &PathBuf, _sub_args: &ArgMatches) -> Result<(), Box<dyn Error>>
 
     // Check if there is a database, if not, create it!
     println!("Trying to connect to the archive file index (database).");
-    let arch = match Archive::connect(root) {
+    let arch = match Archive::connect(&root) {
         Ok(arch) => {
             println!("Found the archive file index. Moving on.\n");
             arch
@@ -34,7 +34,7 @@ pub fn fix(root: &PathBuf, _sub_args: &ArgMatches) -> Result<(), Box<dyn Error>>
                 "Error connecting to archive database {}. Trying to create a new database.\n",
                 err
             );
-            Archive::create(root)?
+            Archive::create(&root)?
         }
     };
 
@@ -2,10 +2,14 @@ use bfkmd::bail;
 use bufkit_data::{Archive, Model};
 use clap::ArgMatches;
 use sounding_bufkit::BufkitFile;
-use std::{error::Error, path::PathBuf, str::FromStr};
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-pub fn import(root: &PathBuf, sub_args: &ArgMatches) -> Result<(), Box<dyn Error>> {
-    let arch = Archive::connect(root)?;
+pub fn import(root: &Path, sub_args: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let arch = Archive::connect(&root)?;
 
     // unwrap is ok, these are required.
     let site_id = sub_args.value_of("site").unwrap();
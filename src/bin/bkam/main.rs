@@ -1,24 +1,28 @@
 //! BufKit Archive Manager
+use bfkmd::OutputFormat;
 use clap::{Arg, Command, crate_version};
 use dirs::home_dir;
 use std::{error::Error, path::PathBuf};
 
 mod copy;
+mod coverage;
 mod create;
 mod export;
 mod fix;
 mod import;
 mod purge;
+mod site_meta;
 mod sites;
+mod stats;
 
 fn main() {
     if let Err(e) = run() {
-        println!("error: {}", e);
+        bfkmd::report_error(&format!("error: {}", e));
 
         let mut err = &*e;
 
         while let Some(cause) = err.source() {
-            println!("caused by: {}", cause);
+            bfkmd::report_error(&format!("caused by: {}", cause));
             err = cause;
         }
 
@@ -77,14 +81,39 @@ fn run() -> Result<(), Box<dyn Error>> {
                                 .short('a')
                                 .help(
                                     "Only list sites that are automatically downloaded by bufdn.",
-                                ),
+                                )
+                                .long_help(concat!(
+                                    "Only list sites that are automatically downloaded by bufdn,",
+                                    " on any auto download list unless --list is given."
+                                )),
                         ).arg(
                             Arg::new("no-auto-download")
                                 .long("no-auto-download")
                                 .short('n')
                                 .help(
-                                    "Only list sites that are automatically downloaded by bufdn.",
-                                ),
+                                    "Only list sites that are not automatically downloaded by bufdn.",
+                                )
+                                .long_help(concat!(
+                                    "Only list sites that are not automatically downloaded by",
+                                    " bufdn, on any auto download list unless --list is given."
+                                )),
+                        ).arg(
+                            Arg::new("list")
+                                .long("list")
+                                .takes_value(true)
+                                .help("Only list sites on this named auto download list.")
+                                .long_help(concat!(
+                                    "Use only this named auto download list instead of every",
+                                    " list. Unless --no-auto-download is also given, only sites",
+                                    " on the list are shown."
+                                )),
+                        ).arg(
+                            Arg::new("format")
+                                .long("format")
+                                .takes_value(true)
+                                .possible_values(OutputFormat::NAMES)
+                                .default_value("table")
+                                .help("The output format."),
                         ).arg(
                             Arg::new("latitude")
                                 .long("latitude")
@@ -97,16 +126,130 @@ fn run() -> Result<(), Box<dyn Error>> {
                                 .help("Only list sites near this location.")
                                 .takes_value(true)
                                 .requires("latitude"),
+                        ).arg(
+                            Arg::new("radius")
+                                .long("radius")
+                                .takes_value(true)
+                                .requires("latitude")
+                                .help("Only list sites within this many miles of the location.")
+                                .long_help(concat!(
+                                    "Only list sites within this many miles of the location given",
+                                    " with --latitude and --longitude, closest first."
+                                )),
+                        ).arg(
+                            Arg::new("bbox")
+                                .long("bbox")
+                                .takes_value(true)
+                                .allow_hyphen_values(true)
+                                .value_name("MINLAT,MINLON,MAXLAT,MAXLON")
+                                .help("Only list sites inside this bounding box."),
+                        ).arg(
+                            Arg::new("limit")
+                                .long("limit")
+                                .takes_value(true)
+                                .help("List at most this many sites."),
+                        ).arg(
+                            Arg::new("last-data")
+                                .long("last-data")
+                                .help("Show the newest data for each model and how old it is.")
+                                .long_help(concat!(
+                                    "Show the newest init time for each model, the hours since the",
+                                    " newest data for any model, and mark the automatically downloaded",
+                                    " sites that are behind schedule as stale."
+                                )),
+                        ).arg(
+                            Arg::new("stale")
+                                .long("stale")
+                                .help("Only list automatically downloaded sites that are behind schedule.")
+                                .long_help(concat!(
+                                    "Only list automatically downloaded sites that are behind schedule.",
+                                    " A site is stale when a model it downloads has missed more than one",
+                                    " run, allowing a few hours for the newest run to be downloaded. Models",
+                                    " the site has never had data for are skipped, but a site with no data",
+                                    " for any of its models is stale. Sites outside their download window",
+                                    " are never stale. Every auto download",
+                                    " list is checked, unless --list picks one. This also shows the",
+                                    " columns from --last-data."
+                                )),
+                        ).arg(
+                            Arg::new("sort")
+                                .long("sort")
+                                .takes_value(true)
+                                .possible_values(["stn", "id", "name", "state", "files", "distance", "last-data"])
+                                .help("Sort the sites by this column.")
+                                .long_help(concat!(
+                                    "Sort the sites by this column instead of by state and station",
+                                    " number, or by distance when a latitude and longitude are given.",
+                                    " Sites missing the value are listed last, and sites without any",
+                                    " data are the oldest for 'last-data'. Sorting by 'distance' needs",
+                                    " --latitude and --longitude."
+                                )),
+                        ).arg(
+                            Arg::new("reverse")
+                                .long("reverse")
+                                .help("Reverse the order of the sites, sites missing the sorted value stay last."),
                         ),
                 ).subcommand(
                     Command::new("modify")
-                        .about("Modify the entry for a site.")
+                        .about("Modify the entry for a site, or every site matching some filters.")
+                        .long_about(concat!(
+                            "Modify the entry for a site. Without a site, the changes apply to every",
+                            " site matching the filters --in-state, --missing-state, --model, and",
+                            " --latitude, --longitude, and --radius. The matching sites are listed",
+                            " for confirmation before anything changes, e.g.",
+                            " 'bkam sites modify --in-state MT ID --auto-download yes'."
+                        ))
                         .arg(
                             Arg::new("stn")
                                 .index(1)
-                                .required(true)
                                 .takes_value(true)
+                                .required_unless_present_any(["in-state", "missing-state", "model", "latitude"])
+                                .conflicts_with_all(&["in-state", "missing-state", "model", "latitude"])
                                 .help("The station number or identifier of the site to modify."),
+                        ).arg(
+                            Arg::new("in-state")
+                                .long("in-state")
+                                .takes_value(true)
+                                .multiple_values(true)
+                                .help("Modify every site in these states, e.g. '--in-state MT ID'."),
+                        ).arg(
+                            Arg::new("missing-state")
+                                .long("missing-state")
+                                .help("Modify every site without a state."),
+                        ).arg(
+                            Arg::new("model")
+                                .long("model")
+                                .takes_value(true)
+                                .help("Modify every site with data for this model."),
+                        ).arg(
+                            Arg::new("latitude")
+                                .long("latitude")
+                                .takes_value(true)
+                                .allow_hyphen_values(true)
+                                .requires_all(&["longitude", "radius"])
+                                .help("Modify every site within the radius of this latitude."),
+                        ).arg(
+                            Arg::new("longitude")
+                                .long("longitude")
+                                .takes_value(true)
+                                .allow_hyphen_values(true)
+                                .requires("latitude")
+                                .help("Modify every site within the radius of this longitude."),
+                        ).arg(
+                            Arg::new("radius")
+                                .long("radius")
+                                .takes_value(true)
+                                .requires("latitude")
+                                .help("The radius in miles around the latitude and longitude."),
+                        ).arg(
+                            Arg::new("dry-run")
+                                .long("dry-run")
+                                .help("List the sites that would change without changing them."),
+                        ).arg(
+                            Arg::new("yes")
+                                .short('y')
+                                .long("yes")
+                                .help("Don't ask for confirmation before modifying several sites."),
                         ).arg(
                             Arg::new("state")
                                 .long("state")
@@ -126,14 +269,103 @@ fn run() -> Result<(), Box<dyn Error>> {
                             Arg::new("auto-download")
                                 .long("auto-download")
                                 .help("Set whether or not to automatically download this site.")
-                                .possible_values(&["Yes", "yes", "no", "No"])
+                                .possible_values(["Yes", "yes", "no", "No"])
                                 .takes_value(true),
+                        ).arg(
+                            Arg::new("auto-download-models")
+                                .long("auto-download-models")
+                                .takes_value(true)
+                                .multiple_values(true)
+                                .conflicts_with("auto-download")
+                                .help("Only automatically download these models for this site.")
+                                .long_help(concat!(
+                                    "Automatically download only these models for this site, e.g.",
+                                    " '--auto-download-models nam4km gfs'. This adds the site to the",
+                                    " auto download list if it isn't already there. Use 'all' to",
+                                    " download every model again."
+                                )),
+                        ).arg(
+                            Arg::new("auto-download-window")
+                                .long("auto-download-window")
+                                .takes_value(true)
+                                .min_values(1)
+                                .max_values(2)
+                                .conflicts_with("auto-download")
+                                .help("Only automatically download this site during this window.")
+                                .long_help(concat!(
+                                    "Only automatically download this site between these dates,",
+                                    " inclusive. Use YYYY-MM-DD for a fixed range of dates, or",
+                                    " MM-DD for a season that repeats every year, e.g.",
+                                    " '--auto-download-window 05-01 10-31'. This adds the site to",
+                                    " the auto download list if it isn't already there. Use",
+                                    " 'always' to remove the window."
+                                )),
+                        ).arg(
+                            Arg::new("list")
+                                .long("list")
+                                .takes_value(true)
+                                .help("The named auto download list to modify, default is 'default'."),
                         ).arg(
                             Arg::new("utc-offset")
                                 .long("utc-offset")
                                 .help("Set the UTC offset in hours. e.g. '--utc-offset -7' for MST.")
                                 .require_equals(true)
                                 .takes_value(true),
+                        ).arg(
+                            Arg::new("time-zone")
+                                .long("time-zone")
+                                .takes_value(true)
+                                .conflicts_with("utc-offset")
+                                .help("Set the IANA time zone, e.g. America/Denver, or 'none' to remove it.")
+                                .long_help(concat!(
+                                    "Set the IANA time zone, e.g. America/Denver, so local times follow",
+                                    " daylight saving time. This also sets the UTC offset to the standard",
+                                    " time offset of the zone. Use 'none' to remove the zone and go back",
+                                    " to the UTC offset."
+                                )),
+                        ).arg(
+                            Arg::new("coords")
+                                .long("coords")
+                                .takes_value(true)
+                                .allow_hyphen_values(true)
+                                .help("Correct the coordinates, e.g. '--coords 46.92,-114.09', or 'none'.")
+                                .long_help(concat!(
+                                    "Correct the coordinates of the site, e.g. '--coords 46.92,-114.09'.",
+                                    " These are used instead of the coordinates in the files, which are",
+                                    " sometimes wrong. Use 'none' to go back to the coordinates in the",
+                                    " files."
+                                )),
+                        ).arg(
+                            Arg::new("elevation")
+                                .long("elevation")
+                                .takes_value(true)
+                                .allow_hyphen_values(true)
+                                .help("Set the elevation of the site in meters."),
+                        ).arg(
+                            Arg::new("add-id")
+                                .long("add-id")
+                                .takes_value(true)
+                                .multiple_occurrences(true)
+                                .help("Add an identifier for a model, e.g. '--add-id gfs=kmso'.")
+                                .long_help(concat!(
+                                    "Add an identifier for a model, e.g. '--add-id gfs=kmso'. It is used",
+                                    " to download the site instead of the identifier in the files. Use",
+                                    " this option once for each model and identifier. If a model has more",
+                                    " than one, the identifier added last is used for downloads, adding",
+                                    " one again makes it the last one added."
+                                )),
+                        ).arg(
+                            Arg::new("retire-id")
+                                .long("retire-id")
+                                .takes_value(true)
+                                .multiple_occurrences(true)
+                                .help("Stop using an identifier for a model, e.g. '--retire-id gfs=kmso'.")
+                                .long_help(concat!(
+                                    "Stop using an identifier for a model, e.g. '--retire-id gfs=kmso'.",
+                                    " It is no longer listed or used for downloads, but the files already",
+                                    " in the archive are kept. Use this option once for each model and",
+                                    " identifier."
+                                )),
                         ),
                 ).subcommand(
                     Command::new("inv")
@@ -148,14 +380,304 @@ fn run() -> Result<(), Box<dyn Error>> {
                         .arg(
                             Arg::new("model")
                                 .index(2)
+                                .takes_value(true)
+                                .multiple_values(true)
+                                .help("The models to get the inventory for, e.g. gfs or nam or nam4km")
+                                .long_help(concat!(
+                                    "The models to get the inventory for, e.g. gfs or nam or nam4km.",
+                                    " With more than one model, or 'all', or no model at all, the",
+                                    " inventories for the models are compared side by side along with",
+                                    " a list of the dates missing a run for at least one model."
+                                )),
+                        ).arg(
+                            Arg::new("format")
+                                .long("format")
+                                .takes_value(true)
+                                .possible_values(OutputFormat::NAMES)
+                                .default_value("table")
+                                .help("The output format, json includes every missing init time."),
+                        ).arg(
+                            Arg::new("calendar")
+                                .long("calendar")
+                                .help("Show the inventory as a grid of days and cycles for each month.")
+                                .long_help(concat!(
+                                    "Show the inventory as a grid with a row for each cycle of every month",
+                                    " and a column for each day, marking the present, partially missing,",
+                                    " and missing cycles. Only used with the table format."
+                                )),
+                        ),
+                ).subcommand(
+                    Command::new("search")
+                        .about("Search for sites by station number, id, name, or notes.")
+                        .long_about(concat!(
+                            "Search for sites by station number, id, name, or notes. The best",
+                            " matches are listed first, and small typos are tolerated."
+                        ))
+                        .arg(
+                            Arg::new("query")
+                                .index(1)
                                 .required(true)
                                 .takes_value(true)
-                                .help("The model to get the inventory for, e.g. gfs or nam or nam4km"),
+                                .help("What to search for, e.g. kmso or missoula."),
+                        ).arg(
+                            Arg::new("limit")
+                                .long("limit")
+                                .takes_value(true)
+                                .help("The most matches to show, default is 10."),
+                        ),
+                ).subcommand(
+                    Command::new("migrate-time-zones")
+                        .about("Set IANA time zones for sites that only have a UTC offset.")
+                        .long_about(concat!(
+                            "Set IANA time zones for sites that only have a UTC offset, so local",
+                            " times follow daylight saving time. The zone is picked from the state",
+                            " and standard time offset of the site, sites where no zone matches are",
+                            " listed so they can be set with 'bkam sites modify --time-zone'."
+                        ))
+                        .arg(
+                            Arg::new("dry-run")
+                                .long("dry-run")
+                                .help("List the time zones without setting them."),
+                        ).arg(
+                            Arg::new("yes")
+                                .short('y')
+                                .long("yes")
+                                .help("Don't ask for confirmation."),
+                        ),
+                ).subcommand(
+                    Command::new("autofill")
+                        .about("Fill in missing states, names, and time zones from coordinates.")
+                        .long_about(concat!(
+                            "Fill in missing states, names, and time zones from the coordinates of",
+                            " each site using the list of stations built into the program. The",
+                            " nearest station gives a name like '12 mi SW of Missoula'. The list",
+                            " has stations but no state or time zone boundaries, so the state is",
+                            " only filled in for sites within 10 miles of a station and the time",
+                            " zone for sites within 50 miles, and neither is filled in where a",
+                            " station in another state or time zone is almost as close. Many sites",
+                            " away from the stations are left without a state, and a site right",
+                            " next to a station across a state line can still get the wrong one.",
+                            " Check the states and time zones of sites near a line. Only missing",
+                            " values are filled in, values that are already set are never changed.",
+                            " The changes are listed for review before anything is saved."
+                        ))
+                        .arg(
+                            Arg::new("max-distance")
+                                .long("max-distance")
+                                .takes_value(true)
+                                .default_value("50")
+                                .help("Only use stations within this many miles of a site."),
+                        ).arg(
+                            Arg::new("dry-run")
+                                .long("dry-run")
+                                .help("List the values without setting them."),
+                        ).arg(
+                            Arg::new("yes")
+                                .short('y')
+                                .long("yes")
+                                .help("Don't ask for confirmation."),
+                        ),
+                ).subcommand(
+                    Command::new("show")
+                        .about("Show everything known about a site.")
+                        .long_about(concat!(
+                            "Show everything known about a site, its metadata, the ids used by",
+                            " each model, its coordinates, the model runs in the archive, the",
+                            " auto download lists it is on, and the URLs the downloader found",
+                            " missing."
+                        ))
+                        .arg(
+                            Arg::new("stn")
+                                .index(1)
+                                .required(true)
+                                .takes_value(true)
+                                .help("The station number or identifier of the site."),
+                        ),
+                ).subcommand(
+                    Command::new("add")
+                        .about("Register a new site before downloading any data for it.")
+                        .arg(
+                            Arg::new("stn")
+                                .index(1)
+                                .required(true)
+                                .takes_value(true)
+                                .help("The station number of the new site."),
+                        ).arg(
+                            Arg::new("id")
+                                .long("id")
+                                .required(true)
+                                .takes_value(true)
+                                .multiple_occurrences(true)
+                                .help("A model and identifier for the site, e.g. '--id gfs=kmso'.")
+                                .long_help(concat!(
+                                    "A model and the identifier used for the site with that model,",
+                                    " e.g. '--id gfs=kmso'. Use this option once for each model and",
+                                    " identifier."
+                                )),
+                        ).arg(
+                            Arg::new("latitude")
+                                .long("latitude")
+                                .required(true)
+                                .takes_value(true)
+                                .allow_hyphen_values(true)
+                                .help("The latitude of the site."),
+                        ).arg(
+                            Arg::new("longitude")
+                                .long("longitude")
+                                .required(true)
+                                .takes_value(true)
+                                .allow_hyphen_values(true)
+                                .help("The longitude of the site, -180 to 180."),
+                        ).arg(
+                            Arg::new("elevation")
+                                .long("elevation")
+                                .takes_value(true)
+                                .allow_hyphen_values(true)
+                                .help("The elevation of the site in meters."),
+                        ).arg(
+                            Arg::new("name")
+                                .long("name")
+                                .takes_value(true)
+                                .help("The name of the site."),
+                        ).arg(
+                            Arg::new("state")
+                                .long("state")
+                                .takes_value(true)
+                                .help("The state or providence of the site."),
+                        ).arg(
+                            Arg::new("notes")
+                                .long("notes")
+                                .takes_value(true)
+                                .help("Notes about the site."),
+                        ).arg(
+                            Arg::new("utc-offset")
+                                .long("utc-offset")
+                                .help("The UTC offset in hours. e.g. '--utc-offset -7' for MST.")
+                                .require_equals(true)
+                                .takes_value(true),
+                        ).arg(
+                            Arg::new("time-zone")
+                                .long("time-zone")
+                                .takes_value(true)
+                                .conflicts_with("utc-offset")
+                                .help("The IANA time zone, e.g. America/Denver. Sets the UTC offset too."),
+                        ).arg(
+                            Arg::new("auto-download")
+                                .long("auto-download")
+                                .help("Automatically download this site with bufdn."),
+                        ).arg(
+                            Arg::new("list")
+                                .long("list")
+                                .takes_value(true)
+                                .requires("auto-download")
+                                .help("The named auto download list to add the site to."),
+                        ).after_help(concat!(
+                            "The identifiers and coordinates are kept until the first download for",
+                            " the site, after that they come from the downloaded files."
+                        )),
+                ).subcommand(
+                    Command::new("merge")
+                        .about("Merge a site into another, or renumber it.")
+                        .long_about(concat!(
+                            "Move all the files for the OLD site to the NEW site and delete the OLD",
+                            " site. The name, state, notes, and UTC offset of the OLD site are used",
+                            " where the NEW site doesn't have them, and the OLD site's auto download",
+                            " settings move with it. If the NEW site doesn't exist yet, the OLD site",
+                            " is renumbered. When both sites have the same model run, the NEW",
+                            " site's file is kept."
+                        ))
+                        .arg(
+                            Arg::new("old")
+                                .index(1)
+                                .required(true)
+                                .takes_value(true)
+                                .help("The station number or identifier of the site to merge."),
+                        ).arg(
+                            Arg::new("new")
+                                .index(2)
+                                .required(true)
+                                .takes_value(true)
+                                .help("The station number or identifier of the site to merge into."),
+                        ).arg(
+                            Arg::new("dry-run")
+                                .long("dry-run")
+                                .help("Show what would be moved without changing anything."),
+                        ),
+                ).subcommand(
+                    Command::new("export-meta")
+                        .about("Export the metadata for all sites to a CSV or JSON file.")
+                        .arg(
+                            Arg::new("file")
+                                .index(1)
+                                .takes_value(true)
+                                .help("The file to write to, or '-' for stdout which is the default."),
+                        ).arg(
+                            Arg::new("format")
+                                .long("format")
+                                .takes_value(true)
+                                .possible_values(["csv", "json"])
+                                .help("The file format, default is from the file extension or csv."),
+                        ).arg(
+                            Arg::new("missing-data")
+                                .short('m')
+                                .long("missing-data")
+                                .help("Only sites with missing info."),
+                        ).arg(
+                            Arg::new("list")
+                                .long("list")
+                                .takes_value(true)
+                                .help("The named auto download list for the auto_download column."),
+                        ),
+                ).subcommand(
+                    Command::new("import-meta")
+                        .about("Update the metadata for sites from a CSV or JSON file.")
+                        .long_about(concat!(
+                            "Update the ids, name, state, UTC offset, time zone, notes, and auto",
+                            " download setting of sites from a file in the format written by",
+                            " export-meta. Empty values clear a field, except the ids which are",
+                            " left unchanged. The ids are MODEL=ID pairs, ids missing from the list",
+                            " are retired and new ones are registered. The auto_download_lists",
+                            " column is only for reference and is not imported. The sites must",
+                            " already be in the archive. The changes are shown and confirmed before",
+                            " they are applied."
+                        ))
+                        .arg(
+                            Arg::new("file")
+                                .index(1)
+                                .required(true)
+                                .takes_value(true)
+                                .help("The file to read, or '-' for stdin."),
+                        ).arg(
+                            Arg::new("format")
+                                .long("format")
+                                .takes_value(true)
+                                .possible_values(["csv", "json"])
+                                .help("The file format, default is from the file extension or csv."),
+                        ).arg(
+                            Arg::new("list")
+                                .long("list")
+                                .takes_value(true)
+                                .help("The named auto download list for the auto_download column."),
+                        ).arg(
+                            Arg::new("dry-run")
+                                .long("dry-run")
+                                .help("Only show the changes."),
+                        ).arg(
+                            Arg::new("yes")
+                                .long("yes")
+                                .short('y')
+                                .conflicts_with("dry-run")
+                                .help("Apply the changes without asking."),
                         ),
                 ),
         ).subcommand(
             Command::new("export")
-                .about("Export a sounding from the database")
+                .about("Export soundings from the database")
+                .long_about(concat!(
+                    "Export soundings for one or more sites and models. Select the sites with a",
+                    " site argument, --sites, --state, or --auto-download, they are combined. Without",
+                    " --start only the most recent run is exported."
+                ))
                 .arg(
                     Arg::new("start")
                         .long("start")
@@ -183,20 +705,92 @@ fn run() -> Result<(), Box<dyn Error>> {
                         .takes_value(false)
                         .help("Do not prefix the date in YYYYMMDDHHZ format to the file name.")
                 ).arg(
-                    Arg::new("site")
+                    Arg::new("name-template")
+                        .long("name-template")
+                        .takes_value(true)
+                        .conflicts_with("no-prefix-date")
+                        .help("Name the files with this template, e.g. '{site}_{model}_{cycle}Z.buf'.")
+                        .long_help(concat!(
+                            "Name the files with this template instead of",
+                            " YYYYMMDDHHZ_model_SITE.buf, e.g. '--name-template",
+                            " {init:%Y%m%d%H}.{site}.{model}.buf'. The placeholders are {site} for",
+                            " the id, {stn} for the station number, {model}, {state}, {init} for",
+                            " the init time as YYYYMMDDHHZ, {init:FORMAT} for the init time in a",
+                            " strftime format, {cycle} for the hour of the init time, and {year}",
+                            " and {month}."
+                        )),
+                ).arg(
+                    Arg::new("args")
                         .index(1)
-                        .required(true)
-                        .help("The site to export data for."),
+                        .required_unless_present("bundle")
+                        .multiple_values(true)
+                        .max_values(3)
+                        .value_name("ARGS")
+                        .help("The target directory, or a site, a model, and the target directory.")
+                        .long_help(concat!(
+                            "The target directory to save the files into. For a single site and",
+                            " model, give the site and the model before the target directory, e.g.",
+                            " 'bkam export kmso gfs ./out'. The site and model may be comma",
+                            " separated lists, e.g. 'kmso,kgpi nam,gfs'. Use '-' as the target",
+                            " to write a single run to stdout. With --bundle leave off the target."
+                        )),
                 ).arg(
-                    Arg::new("model")
-                        .index(2)
-                        .required(true)
-                        .help("The model to export data for, e.g. gfs, GFS, NAM4KM, nam."),
+                    Arg::new("sites")
+                        .short('s')
+                        .long("sites")
+                        .takes_value(true)
+                        .use_value_delimiter(true)
+                        .multiple_occurrences(true)
+                        .help("Export these sites, by station number or id, e.g. '--sites kmso,kgpi'."),
                 ).arg(
-                    Arg::new("target")
-                        .index(3)
-                        .required(true)
-                        .help("Target directory to save the export file into."),
+                    Arg::new("models")
+                        .short('m')
+                        .long("models")
+                        .takes_value(true)
+                        .use_value_delimiter(true)
+                        .multiple_occurrences(true)
+                        .help("Export these models, default is all of them, e.g. '--models gfs,nam4km'."),
+                ).arg(
+                    Arg::new("state")
+                        .long("state")
+                        .takes_value(true)
+                        .use_value_delimiter(true)
+                        .multiple_occurrences(true)
+                        .help("Export every site in these states, e.g. '--state MT,ID'."),
+                ).arg(
+                    Arg::new("auto-download")
+                        .short('a')
+                        .long("auto-download")
+                        .help("Export every site on the auto download list."),
+                ).arg(
+                    Arg::new("list")
+                        .long("list")
+                        .takes_value(true)
+                        .requires("auto-download")
+                        .help("The named auto download list to use, default is 'default'."),
+                ).arg(
+                    Arg::new("layout")
+                        .long("layout")
+                        .takes_value(true)
+                        .help("Save the files in sub directories, e.g. '{model}/{site}/'.")
+                        .long_help(concat!(
+                            "Save the files in sub directories of the target directory following",
+                            " this layout, e.g. '--layout {model}/{site}/'. The placeholders are",
+                            " the same as for --name-template. By default all the files go",
+                            " directly in the target directory."
+                        )),
+                ).arg(
+                    Arg::new("bundle")
+                        .long("bundle")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Pack the files into a .tar.gz or .zip file instead of a directory.")
+                        .long_help(concat!(
+                            "Pack the exported files into a single compressed file instead of",
+                            " saving them in a target directory, e.g. '--bundle kmso.tar.gz'. The",
+                            " format comes from the extension, .tar.gz, .tgz, or .zip. The",
+                            " --layout and --name-template set the paths inside the bundle."
+                        )),
                 ),
         ).subcommand(
             Command::new("import")
@@ -310,6 +904,84 @@ fn run() -> Result<(), Box<dyn Error>> {
         ).subcommand(
             Command::new("fix")
                 .about("Find and fix inconsistencies in the archive.")
+        ).subcommand(
+            Command::new("stats")
+                .about("Summarize the size and contents of the archive.")
+                .long_about(concat!(
+                    "Summarize the number of sites and files in the archive, the disk space used",
+                    " by model, month, and site, and how fast the archive has grown over the last",
+                    " 30 days. The growth is based on when the files were last modified."
+                ))
+                .arg(
+                    Arg::new("sites")
+                        .short('s')
+                        .long("sites")
+                        .takes_value(true)
+                        .multiple_values(true)
+                        .help("Only include these sites, by station number or id."),
+                ).arg(
+                    Arg::new("models")
+                        .short('m')
+                        .long("models")
+                        .takes_value(true)
+                        .multiple_values(true)
+                        .help("Only include these models, e.g. gfs, GFS, NAM4KM, nam."),
+                ).arg(
+                    Arg::new("state")
+                        .long("state")
+                        .takes_value(true)
+                        .help("Only include sites in the given state."),
+                ).arg(
+                    Arg::new("top")
+                        .long("top")
+                        .takes_value(true)
+                        .help("The number of sites to list by size, default is 20."),
+                )
+        ).subcommand(
+            Command::new("coverage")
+                .about("Show the percent of expected model runs in the archive by month.")
+                .long_about(concat!(
+                    "Show the percent of the expected model runs that are in the archive for each",
+                    " site, model, and month. Runs after the current time are not expected. By",
+                    " default the last 12 months are shown."
+                ))
+                .arg(
+                    Arg::new("sites")
+                        .short('s')
+                        .long("sites")
+                        .takes_value(true)
+                        .multiple_values(true)
+                        .help("Only include these sites, by station number or id."),
+                ).arg(
+                    Arg::new("models")
+                        .short('m')
+                        .long("models")
+                        .takes_value(true)
+                        .multiple_values(true)
+                        .help("Only include these models, e.g. gfs, GFS, NAM4KM, nam."),
+                ).arg(
+                    Arg::new("state")
+                        .long("state")
+                        .takes_value(true)
+                        .help("Only include sites in the given state."),
+                ).arg(
+                    Arg::new("start")
+                        .long("start")
+                        .takes_value(true)
+                        .help("The first month to show. YYYY-MM"),
+                ).arg(
+                    Arg::new("end")
+                        .long("end")
+                        .takes_value(true)
+                        .help("The last month to show, default is the current month. YYYY-MM"),
+                ).arg(
+                    Arg::new("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(OutputFormat::NAMES)
+                        .default_value("table")
+                        .help("The output format, table is a heat map."),
+                )
         );
 
     let matches = app.get_matches();
@@ -328,6 +1000,8 @@ fn run() -> Result<(), Box<dyn Error>> {
         Some(("purge", sub_args)) => purge::purge(root, sub_args)?,
         Some(("fix", sub_args)) => fix::fix(root, sub_args)?,
         Some(("copy", sub_args)) => copy::copy(root, sub_args)?,
+        Some(("stats", sub_args)) => stats::stats(root, sub_args)?,
+        Some(("coverage", sub_args)) => coverage::coverage(root, sub_args)?,
         _ => unreachable!(),
     }
 
@@ -0,0 +1,451 @@
+use bfkmd::{AutoDownloadListDb, SiteRegistryDb, TablePrinter, bail};
+use bufkit_data::{Archive, BufkitDataErr, Model, SiteInfo, StateProv, StationNumber};
+use chrono::FixedOffset;
+use chrono_tz::Tz;
+use clap::ArgMatches;
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufRead, Read, Write},
+    path::Path,
+    str::FromStr,
+};
+use strum::IntoEnumIterator;
+
+/// The metadata for a site as it is written to and read from a file.
+#[derive(Debug, Serialize, Deserialize)]
+struct SiteMeta {
+    station_num: u32,
+    /// Comma separated MODEL=ID pairs, empty leaves the ids unchanged on import.
+    #[serde(default)]
+    ids: String,
+    name: Option<String>,
+    state: Option<String>,
+    /// As +HH:MM or -HH:MM.
+    utc_offset: Option<String>,
+    /// An IANA time zone name, e.g. America/Denver.
+    #[serde(default)]
+    time_zone: Option<String>,
+    notes: Option<String>,
+    /// Whether the site is on the auto download list selected with --list.
+    auto_download: bool,
+    /// Comma separated names of every auto download list the site is on. This is only for
+    /// reference, import changes the selected list through `auto_download`.
+    #[serde(default)]
+    auto_download_lists: String,
+}
+
+/// An id and the model it is used for.
+type ModelId = (Model, String);
+
+#[derive(Debug, Clone, Copy)]
+enum MetaFormat {
+    Csv,
+    Json,
+}
+
+pub fn export_meta(root: &Path, sub_sub_args: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let arch = Archive::connect(&root)?;
+    let dl_db = AutoDownloadListDb::open_or_create(root)?.with_list(list_arg(sub_sub_args));
+    let registry = SiteRegistryDb::open_or_create(root)?;
+
+    let path = sub_sub_args
+        .value_of("file")
+        .filter(|&path| path != "-")
+        .map(Path::new);
+    let format = meta_format(sub_sub_args, path);
+
+    let mut sites = arch.sites()?;
+    if sub_sub_args.is_present("missing-data") {
+        sites.retain(SiteInfo::incomplete);
+    }
+    sites.sort_unstable_by_key(|site| Into::<u32>::into(site.station_num));
+
+    let mut records = Vec::with_capacity(sites.len());
+    for site in sites {
+        let site_ids = ids_in_use(&arch, &registry, site.station_num)?;
+
+        records.push(SiteMeta {
+            station_num: site.station_num.into(),
+            ids: format_ids(&site_ids),
+            name: site.name,
+            state: site.state.map(|st| st.as_static_str().to_owned()),
+            utc_offset: site.time_zone.map(|tz| tz.to_string()),
+            time_zone: registry
+                .time_zone_for(site.station_num)?
+                .map(|zone| zone.name().to_owned()),
+            notes: site.notes,
+            auto_download: dl_db.is_auto_downloaded(site.station_num)?,
+            auto_download_lists: dl_db
+                .entries_for_site(site.station_num)?
+                .into_iter()
+                .map(|entry| entry.list)
+                .collect::<Vec<_>>()
+                .join(","),
+        });
+    }
+
+    let writer: Box<dyn Write> = match path {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+
+    match format {
+        MetaFormat::Csv => {
+            let mut wtr = csv::Writer::from_writer(writer);
+            for record in &records {
+                wtr.serialize(record)?;
+            }
+            wtr.flush()?;
+        }
+        MetaFormat::Json => {
+            let mut writer = writer;
+            serde_json::to_writer_pretty(&mut writer, &records)?;
+            writeln!(writer)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn import_meta(root: &Path, sub_sub_args: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let arch = Archive::connect(&root)?;
+    let dl_db = AutoDownloadListDb::open_or_create(root)?.with_list(list_arg(sub_sub_args));
+    let registry = SiteRegistryDb::open_or_create(root)?;
+
+    // Safe to unwrap because the argument is required.
+    let path = sub_sub_args.value_of("file").unwrap();
+    let path = if path == "-" { None } else { Some(Path::new(path)) };
+    let format = meta_format(sub_sub_args, path);
+
+    let reader: Box<dyn Read> = match path {
+        Some(path) => Box::new(File::open(path)?),
+        None => Box::new(io::stdin()),
+    };
+
+    let records: Vec<SiteMeta> = match format {
+        MetaFormat::Csv => csv::Reader::from_reader(reader)
+            .deserialize()
+            .collect::<Result<_, _>>()?,
+        MetaFormat::Json => serde_json::from_reader(reader)?,
+    };
+
+    let mut changes: Vec<[String; 4]> = vec![];
+    let mut updated_sites: Vec<SiteInfo> = vec![];
+    let mut zone_changes: Vec<(StationNumber, Option<Tz>)> = vec![];
+    let mut auto_download_changes: Vec<(StationNumber, bool)> = vec![];
+    let mut id_changes: Vec<(StationNumber, Vec<ModelId>, Vec<ModelId>)> = vec![];
+    let mut errors: Vec<String> = vec![];
+
+    for record in records {
+        let station_num = StationNumber::from(record.station_num);
+        let old_site = match arch.site(station_num) {
+            Some(site) => site,
+            None => {
+                errors.push(format!(
+                    "Site {} is not in the archive, add it with 'bkam sites add'.",
+                    station_num
+                ));
+                continue;
+            }
+        };
+
+        let state = non_empty(record.state).map(|st| StateProv::from_str(&st.to_uppercase()));
+        let state = match state {
+            Some(Ok(state)) => Some(state),
+            Some(Err(_)) => {
+                errors.push(format!("Invalid state for site {}.", station_num));
+                continue;
+            }
+            None => None,
+        };
+
+        let time_zone = non_empty(record.utc_offset).map(|tz| FixedOffset::from_str(&tz));
+        let time_zone = match time_zone {
+            Some(Ok(time_zone)) => Some(time_zone),
+            Some(Err(_)) => {
+                errors.push(format!("Invalid UTC offset for site {}.", station_num));
+                continue;
+            }
+            None => None,
+        };
+
+        let zone = non_empty(record.time_zone).map(|zone| Tz::from_str(&zone));
+        let zone = match zone {
+            Some(Ok(zone)) => Some(zone),
+            Some(Err(_)) => {
+                errors.push(format!("Invalid time zone for site {}.", station_num));
+                continue;
+            }
+            None => None,
+        };
+
+        let ids = match parse_ids(&record.ids) {
+            Some(ids) => ids,
+            None => {
+                errors.push(format!("Invalid ids for site {}, use MODEL=ID.", station_num));
+                continue;
+            }
+        };
+
+        let mut ids_taken = false;
+        for (model, id) in &ids {
+            if let Some(other_stn) = id_used_by(&arch, &registry, id, *model)?
+                && other_stn != station_num
+            {
+                errors.push(format!(
+                    "The id {} for site {} is already used by site {} for {}.",
+                    id, station_num, other_stn, model
+                ));
+                ids_taken = true;
+            }
+        }
+        if ids_taken {
+            continue;
+        }
+
+        let new_site = SiteInfo {
+            station_num,
+            name: non_empty(record.name),
+            notes: non_empty(record.notes),
+            state,
+            time_zone,
+        };
+
+        let mut changed = false;
+        let mut compare = |field: &str, old: Option<String>, new: Option<String>| {
+            if old != new {
+                let blank = || "-".to_owned();
+                changes.push([
+                    station_num.to_string(),
+                    field.to_owned(),
+                    old.unwrap_or_else(blank),
+                    new.unwrap_or_else(blank),
+                ]);
+                changed = true;
+            }
+        };
+
+        compare("Name", old_site.name.clone(), new_site.name.clone());
+        compare(
+            "State",
+            old_site.state.map(|st| st.as_static_str().to_owned()),
+            new_site.state.map(|st| st.as_static_str().to_owned()),
+        );
+        compare(
+            "UTC Offset",
+            old_site.time_zone.map(|tz| tz.to_string()),
+            new_site.time_zone.map(|tz| tz.to_string()),
+        );
+        compare("Notes", old_site.notes.clone(), new_site.notes.clone());
+
+        if changed {
+            updated_sites.push(new_site);
+        }
+
+        let old_zone = registry.time_zone_for(station_num)?;
+        if old_zone != zone {
+            let name = |zone: Option<Tz>| zone.map(|zone| zone.name()).unwrap_or("-").to_owned();
+            changes.push([
+                station_num.to_string(),
+                "Time Zone".to_owned(),
+                name(old_zone),
+                name(zone),
+            ]);
+            zone_changes.push((station_num, zone));
+        }
+
+        // No ids in the file leaves them as they are.
+        let old_ids = ids_in_use(&arch, &registry, station_num)?;
+        if !ids.is_empty() && old_ids != ids {
+            changes.push([
+                station_num.to_string(),
+                "IDs".to_owned(),
+                format_ids(&old_ids),
+                format_ids(&ids),
+            ]);
+
+            let added = ids.iter().filter(|id| !old_ids.contains(id)).cloned().collect();
+            let retired = old_ids.into_iter().filter(|id| !ids.contains(id)).collect();
+            id_changes.push((station_num, added, retired));
+        }
+
+        let old_auto_download = dl_db.is_auto_downloaded(station_num)?;
+        if old_auto_download != record.auto_download {
+            let yes_no = |val: bool| if val { "Yes" } else { "No" }.to_owned();
+            changes.push([
+                station_num.to_string(),
+                format!("Auto Download ({})", dl_db.list()),
+                yes_no(old_auto_download),
+                yes_no(record.auto_download),
+            ]);
+            auto_download_changes.push((station_num, record.auto_download));
+        }
+    }
+
+    if !errors.is_empty() {
+        for error in &errors {
+            println!("{}", error);
+        }
+        bail("No changes were made.");
+    }
+
+    if changes.is_empty() {
+        println!("No changes.");
+        return Ok(());
+    }
+
+    let mut tp = TablePrinter::new()
+        .with_title("Changes to import".to_owned())
+        .with_column::<&str, String>("Stn Num", &[])
+        .with_column::<&str, String>("Field", &[])
+        .with_column::<&str, String>("Old", &[])
+        .with_column::<&str, String>("New", &[]);
+    for change in changes {
+        tp.add_row(change.to_vec());
+    }
+    tp.print()?;
+
+    if sub_sub_args.is_present("dry-run") {
+        println!("Dry run, nothing was changed.");
+        return Ok(());
+    }
+
+    if !sub_sub_args.is_present("yes") && path.is_none() {
+        bail("Use --yes to apply changes read from stdin, there is no way to confirm them.");
+    }
+
+    if !sub_sub_args.is_present("yes") && !confirm("Apply these changes? [y/N] ")? {
+        println!("No changes were made.");
+        return Ok(());
+    }
+
+    for site in &updated_sites {
+        arch.update_site(site)?;
+    }
+
+    for (station_num, zone) in zone_changes {
+        registry.set_time_zone(station_num, zone)?;
+    }
+
+    for (station_num, added, retired) in id_changes {
+        for (model, id) in retired {
+            registry.retire_id(station_num, model, &id)?;
+        }
+        for (model, id) in added {
+            registry.add_id(station_num, model, &id)?;
+        }
+    }
+
+    for (station_num, auto_download) in auto_download_changes {
+        if auto_download {
+            dl_db.add_site(station_num)?;
+        } else {
+            dl_db.remove_site(station_num)?;
+        }
+    }
+
+    println!("Done.");
+
+    Ok(())
+}
+
+fn list_arg(sub_sub_args: &ArgMatches) -> &str {
+    sub_sub_args
+        .value_of("list")
+        .unwrap_or(AutoDownloadListDb::DEFAULT_LIST)
+}
+
+/// Use the format option if given, otherwise go by the file extension. CSV is the default.
+fn meta_format(sub_sub_args: &ArgMatches, path: Option<&Path>) -> MetaFormat {
+    let format = sub_sub_args.value_of("format").or_else(|| {
+        path.and_then(Path::extension)
+            .and_then(|ext| ext.to_str())
+    });
+
+    match format {
+        Some(format) if format.eq_ignore_ascii_case("json") => MetaFormat::Json,
+        _ => MetaFormat::Csv,
+    }
+}
+
+/// The ids a site uses for each model, from the files and the registry, without retired ids.
+fn ids_in_use(
+    arch: &Archive,
+    registry: &SiteRegistryDb,
+    station_num: StationNumber,
+) -> Result<Vec<(Model, String)>, BufkitDataErr> {
+    let retired = registry.retired_ids_for(station_num)?;
+
+    let mut ids: Vec<(Model, String)> = vec![];
+    for model in Model::iter() {
+        for id in arch.ids(station_num, model)? {
+            ids.push((model, id));
+        }
+    }
+    ids.extend(registry.ids_for(station_num)?);
+    ids.retain(|id| !retired.contains(id));
+
+    ids.sort_unstable();
+    ids.dedup();
+
+    Ok(ids)
+}
+
+/// Find the site using an id for a model, in the files or the registry.
+fn id_used_by(
+    arch: &Archive,
+    registry: &SiteRegistryDb,
+    id: &str,
+    model: Model,
+) -> Result<Option<StationNumber>, BufkitDataErr> {
+    match arch.station_num_for_id(id, model) {
+        Ok(station_num) => Ok(Some(station_num)),
+        Err(BufkitDataErr::NotInIndex) => registry.station_num_for_id(id, model),
+        Err(err) => Err(err),
+    }
+}
+
+fn format_ids(ids: &[(Model, String)]) -> String {
+    ids.iter()
+        .map(|(model, id)| format!("{}={}", model.as_static_str(), id))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Parse comma separated MODEL=ID pairs, `None` if any of them are invalid.
+fn parse_ids(ids: &str) -> Option<Vec<(Model, String)>> {
+    let mut parsed: Vec<(Model, String)> = ids
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (model, id) = pair.split_once('=')?;
+            let model = Model::from_str(model.trim()).ok()?;
+            let id = id.trim().to_uppercase();
+
+            (!id.is_empty()).then_some((model, id))
+        })
+        .collect::<Option<_>>()?;
+
+    parsed.sort_unstable();
+    parsed.dedup();
+
+    Some(parsed)
+}
+
+fn non_empty(val: Option<String>) -> Option<String> {
+    val.filter(|val| !val.trim().is_empty())
+}
+
+/// Ask a yes or no question on the terminal, anything but yes is no.
+pub fn confirm(prompt: &str) -> Result<bool, Box<dyn Error>> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+
+    Ok(answer.trim().eq_ignore_ascii_case("y") || answer.trim().eq_ignore_ascii_case("yes"))
+}
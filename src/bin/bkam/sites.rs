@@ -1,24 +1,49 @@
-use bfkmd::{AutoDownloadListDb, TablePrinter, bail};
-use bufkit_data::{Archive, BufkitDataErr, Model, StateProv, StationNumber, StationSummary};
-use chrono::FixedOffset;
+use crate::site_meta;
+use bfkmd::{
+    ArchiveIndex, AutoDownloadEntry, AutoDownloadListDb, DownloadWindow, LocalTimeZone,
+    MissingUrlDb, OutputFormat, RegisteredCoords, SiteRegistryDb, TablePrinter, add_months,
+    bail, haversine_miles,
+};
+use bufkit_data::{
+    Archive, BufkitDataErr, Model, SiteInfo, StateProv, StationNumber, StationSummary,
+};
+use chrono::{Datelike, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
 use clap::ArgMatches;
-use std::{error::Error, path::PathBuf, str::FromStr};
+use serde::{Serialize, Serializer};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    error::Error,
+    path::Path,
+    str::FromStr,
+};
+use strum::IntoEnumIterator;
 
-pub fn sites(root: &PathBuf, sub_args: &ArgMatches) -> Result<(), Box<dyn Error>> {
+pub fn sites(root: &Path, sub_args: &ArgMatches) -> Result<(), Box<dyn Error>> {
     match sub_args.subcommand() {
-        Some(("list", sub_sub_args)) => sites_list(root, sub_args, &sub_sub_args),
-        Some(("modify", sub_sub_args)) => sites_modify(root, sub_args, &sub_sub_args),
-        Some(("inv", sub_sub_args)) => sites_inventory(root, sub_args, &sub_sub_args),
+        Some(("list", sub_sub_args)) => sites_list(root, sub_args, sub_sub_args),
+        Some(("modify", sub_sub_args)) => sites_modify(root, sub_args, sub_sub_args),
+        Some(("inv", sub_sub_args)) => sites_inventory(root, sub_args, sub_sub_args),
+        Some(("add", sub_sub_args)) => sites_add(root, sub_args, sub_sub_args),
+        Some(("merge", sub_sub_args)) => sites_merge(root, sub_args, sub_sub_args),
+        Some(("show", sub_sub_args)) => sites_show(root, sub_args, sub_sub_args),
+        Some(("search", sub_sub_args)) => sites_search(root, sub_args, sub_sub_args),
+        Some(("migrate-time-zones", sub_sub_args)) => {
+            sites_migrate_time_zones(root, sub_args, sub_sub_args)
+        }
+        Some(("autofill", sub_sub_args)) => sites_autofill(root, sub_args, sub_sub_args),
+        Some(("export-meta", sub_sub_args)) => site_meta::export_meta(root, sub_sub_args),
+        Some(("import-meta", sub_sub_args)) => site_meta::import_meta(root, sub_sub_args),
         _ => unreachable!(),
     }
 }
 
 fn sites_list(
-    root: &PathBuf,
+    root: &Path,
     _sub_args: &ArgMatches,
     sub_sub_args: &ArgMatches,
 ) -> Result<(), Box<dyn Error>> {
-    let arch = Archive::connect(root)?;
+    let arch = Archive::connect(&root)?;
 
     //
     // This filter lets all sites pass
@@ -71,24 +96,28 @@ fn sites_list(
         };
 
     //
-    // Filter based on auto download
+    // Filter based on auto download, without --list a site on any list counts
     //
-    let dl_db = AutoDownloadListDb::open_or_create(&arch.root())?;
+    let mut dl_db = AutoDownloadListDb::open_or_create(arch.root())?;
+    if let Some(list) = sub_sub_args.value_of("list") {
+        dl_db = dl_db.with_list(list);
+    }
+    let all_lists = !sub_sub_args.is_present("list");
     let auto_download = &|site: &StationSummary| -> bool {
-        dl_db
-            .is_auto_downloaded(site.station_num)
+        !auto_download_entries(&dl_db, site.station_num, all_lists)
             .expect("Error with AutoDownloadListDb")
+            .is_empty()
     };
     let no_auto_download = &|site: &StationSummary| -> bool {
-        !dl_db
-            .is_auto_downloaded(site.station_num)
+        auto_download_entries(&dl_db, site.station_num, all_lists)
             .expect("Error with AutoDownloadListDb")
+            .is_empty()
     };
     let auto_download_pred: &dyn Fn(&StationSummary) -> bool =
-        if sub_sub_args.is_present("auto-download") {
-            auto_download
-        } else if sub_sub_args.is_present("no-auto-download") {
+        if sub_sub_args.is_present("no-auto-download") {
             no_auto_download
+        } else if sub_sub_args.is_present("auto-download") || sub_sub_args.is_present("list") {
+            auto_download
         } else {
             pass
         };
@@ -96,32 +125,45 @@ fn sites_list(
     //
     // Query the master list and sort it
     //
+    let registry = SiteRegistryDb::open_or_create(arch.root())?;
+    let corrected_coords = registry.has_coords()?;
+
     let mut tlat = 0.0;
     let mut tlon = 0.0;
-    let master_list: Vec<StationSummary> = if sub_sub_args.is_present("latitude") {  // implies longitude is also available
+    let mut master_list: Vec<StationSummary> = if sub_sub_args.is_present("latitude") {  // implies longitude is also available
         let lat = sub_sub_args.value_of("latitude")
-            .ok_or_else(|| BufkitDataErr::GeneralError(format!("Unable to parse latitude")))
+            .ok_or_else(|| BufkitDataErr::GeneralError("Unable to parse latitude".to_string()))
             .and_then(|lat_str| {
                 f64::from_str(lat_str)
                     .map_err(|_| BufkitDataErr::GeneralError(format!("Unable to parse latitude: {}", lat_str)))
             });
 
         let lon = sub_sub_args.value_of("longitude")
-            .ok_or_else(|| BufkitDataErr::GeneralError(format!("Unable to parse longitude")))
+            .ok_or_else(|| BufkitDataErr::GeneralError("Unable to parse longitude".to_string()))
             .and_then(|lon_str| {
                 f64::from_str(lon_str)
                     .map_err(|_| BufkitDataErr::GeneralError(format!("Unable to parse longitude: {}", lon_str)))
             });
 
-        lat
-            .and_then(|lat| lon.map(|lon| (lat, lon)))
-            .and_then(|(lat, lon)| {
-                tlat = lat;
-                tlon = lon;
-                arch.station_summaries_near(lat, lon)
-            })?
+        let (lat, lon) = lat.and_then(|lat| lon.map(|lon| (lat, lon)))?;
+        tlat = lat;
+        tlon = lon;
+
+        // The nearby sites only go out about 35 miles, a radius needs all of them. So do corrected
+        // coordinates, they can be far from the coordinates in the files.
+        if sub_sub_args.is_present("radius") || corrected_coords {
+            let mut master_list = arch.station_summaries()?;
+            let registered = registered_summaries(&arch, &master_list)?;
+            master_list.extend(registered);
+
+            master_list
+        } else {
+            arch.station_summaries_near(lat, lon)?
+        }
     } else {
         let mut master_list = arch.station_summaries()?;
+        let registered = registered_summaries(&arch, &master_list)?;
+        master_list.extend(registered);
 
         master_list.sort_unstable_by(|left, right| {
             let lnum: u32 = left.station_num.into();
@@ -148,22 +190,169 @@ fn sites_list(
         master_list
     };
 
+    // Coordinates and ids corrected with 'bkam sites modify'
+    apply_registry_corrections(&arch, &registry, &mut master_list)?;
+
     // Haversine function in miles for the selected point
-    let distance = move |coords: &(f64, f64)| -> f64 {
-        let (clat, clon) = coords;
-        
-        let dlat = (tlat - clat).to_radians();
-        let dlon = (tlon - clon).to_radians();
+    let distance = move |coords: &(f64, f64)| -> f64 { haversine_miles((tlat, tlon), coords) };
+
+    let min_distance = |site: &StationSummary| -> f64 {
+        site.coords
+            .iter()
+            .map(distance)
+            .fold(f64::INFINITY, f64::min)
+    };
+
+    // Sort by the corrected distance, and keep the same area as the nearby sites from the archive.
+    if sub_sub_args.is_present("latitude") {
+        if !sub_sub_args.is_present("radius") {
+            master_list.retain(|site| {
+                site.coords
+                    .iter()
+                    .any(|&(lat, lon)| (lat - tlat).abs() < 0.5 && (lon - tlon).abs() < 0.5)
+            });
+        }
+        master_list.sort_by(|left, right| min_distance(left).total_cmp(&min_distance(right)));
+    }
+
+    //
+    // Filter based on the distance from the selected point
+    //
+    let radius = sub_sub_args.value_of("radius").map(|radius| match f64::from_str(radius) {
+        Ok(radius) if radius >= 0.0 => radius,
+        _ => bail(&format!("Invalid radius: {}", radius)),
+    });
+    let in_radius = &|site: &StationSummary| -> bool {
+        // Unwrap is safe because this is only used when the radius was given.
+        min_distance(site) <= radius.unwrap()
+    };
+    let in_radius_pred: &dyn Fn(&StationSummary) -> bool = if radius.is_some() {
+        in_radius
+    } else {
+        pass
+    };
+
+    //
+    // Filter based on a bounding box
+    //
+    let bbox: Option<Vec<f64>> = sub_sub_args.value_of("bbox").map(|bbox| {
+        match bbox
+            .split(',')
+            .map(|val| f64::from_str(val.trim()))
+            .collect::<Result<Vec<f64>, _>>()
+        {
+            Ok(vals) if vals.len() == 4 && vals[0] <= vals[2] && vals[1] <= vals[3] => vals,
+            _ => bail(&format!(
+                "Invalid bounding box, use minlat,minlon,maxlat,maxlon: {}",
+                bbox
+            )),
+        }
+    });
+    let in_bbox = &|site: &StationSummary| -> bool {
+        // Unwrap is safe because this is only used when the bounding box was given.
+        let bbox = bbox.as_ref().unwrap();
+        site.coords.iter().any(|&(lat, lon)| {
+            lat >= bbox[0] && lat <= bbox[2] && lon >= bbox[1] && lon <= bbox[3]
+        })
+    };
+    let in_bbox_pred: &dyn Fn(&StationSummary) -> bool = if bbox.is_some() {
+        in_bbox
+    } else {
+        pass
+    };
 
-        let lat = tlat.to_radians();
-        let clat = clat.to_radians();
+    let limit = sub_sub_args.value_of("limit").map_or(usize::MAX, |limit| {
+        limit
+            .parse::<usize>()
+            .unwrap_or_else(|_| bail(&format!("Invalid limit: {}", limit)))
+    });
 
-        let a = f64::powi(f64::sin(dlat / 2.0), 2) + f64::powi(f64::sin(dlon / 2.0), 2) * f64::cos(lat) * f64::cos(clat);
+    //
+    // The newest data for each site, and filter for sites that are behind schedule
+    //
+    let sort_key = sub_sub_args.value_of("sort");
+    let show_last_data = sub_sub_args.is_present("last-data") || sub_sub_args.is_present("stale");
+    let mut last_data: HashMap<StationNumber, LastData> = HashMap::new();
+    if show_last_data || sort_key == Some("last-data") {
+        let latest = ArchiveIndex::open(arch.root())?.most_recent_runs()?;
+        let now = Utc::now().naive_utc();
+        // Without --list a site is stale if it is behind for any list it is on.
+        for site in &master_list {
+            let data = site_last_data(site, &latest, &dl_db, all_lists, now)?;
+            last_data.insert(site.station_num, data);
+        }
+    }
+    let stale = &|site: &StationSummary| -> bool {
+        last_data
+            .get(&site.station_num)
+            .is_some_and(|data| data.stale == Some(true))
+    };
+    let stale_pred: &dyn Fn(&StationSummary) -> bool = if sub_sub_args.is_present("stale") {
+        stale
+    } else {
+        pass
+    };
 
-        let rad = 3958.761;
-        let c = 2.0 * f64::asin(f64::sqrt(a));
-        rad * c
+    //
+    // Sort the list, ties keep the default order
+    //
+    let newest = |site: &StationSummary| {
+        last_data
+            .get(&site.station_num)
+            .and_then(|data| data.runs.values().max().copied())
     };
+    // Reversing only changes the order of the values, sites missing the value stay last.
+    let reverse = sub_sub_args.is_present("reverse");
+    let ordered = |ord: std::cmp::Ordering| if reverse { ord.reverse() } else { ord };
+    match sort_key {
+        None if reverse => master_list.reverse(),
+        None => {}
+        Some("stn") => master_list.sort_by(|left, right| {
+            ordered(Into::<u32>::into(left.station_num).cmp(&Into::<u32>::into(right.station_num)))
+        }),
+        Some("id") => master_list.sort_by(|left, right| {
+            cmp_missing_last(left.ids.first(), right.ids.first(), reverse)
+        }),
+        Some("name") => master_list.sort_by(|left, right| {
+            cmp_missing_last(
+                left.name.as_ref().map(|name| name.to_lowercase()).as_ref(),
+                right.name.as_ref().map(|name| name.to_lowercase()).as_ref(),
+                reverse,
+            )
+        }),
+        Some("state") => master_list.sort_by(|left, right| {
+            cmp_missing_last(
+                left.state.map(|st| st.as_static_str()).as_ref(),
+                right.state.map(|st| st.as_static_str()).as_ref(),
+                reverse,
+            )
+            .then_with(|| {
+                Into::<u32>::into(left.station_num).cmp(&Into::<u32>::into(right.station_num))
+            })
+        }),
+        Some("files") => master_list.sort_by(|left, right| {
+            ordered(left.number_of_files.cmp(&right.number_of_files))
+        }),
+        Some("distance") => {
+            if !sub_sub_args.is_present("latitude") {
+                bail("Sorting by distance needs --latitude and --longitude.");
+            }
+            master_list.sort_by(|left, right| {
+                let (left, right) = (min_distance(left), min_distance(right));
+
+                // Sites without coordinates are infinitely far away.
+                match (left.is_finite(), right.is_finite()) {
+                    (true, true) => ordered(left.total_cmp(&right)),
+                    (left, right) => right.cmp(&left),
+                }
+            })
+        }
+        // Sites without any data are the oldest.
+        Some("last-data") => {
+            master_list.sort_by(|left, right| ordered(newest(left).cmp(&newest(right))))
+        }
+        Some(_) => unreachable!(),
+    }
 
     //
     // Combine filters to make an iterator over the sites.
@@ -171,93 +360,131 @@ fn sites_list(
     let sites_iter = || {
         master_list
             .iter()
-            .filter(|s| missing_any_pred(&s))
-            .filter(|s| missing_state_pred(&s))
-            .filter(|s| in_state_pred(&s))
-            .filter(|s| auto_download_pred(&s))
+            .filter(|s| missing_any_pred(s))
+            .filter(|s| missing_state_pred(s))
+            .filter(|s| in_state_pred(s))
+            .filter(|s| auto_download_pred(s))
+            .filter(|s| stale_pred(s))
+            .filter(|s| in_radius_pred(s))
+            .filter(|s| in_bbox_pred(s))
+            .take(limit)
     };
 
-    let mut table_printer = if sites_iter().count() == 0 {
+    // Safe to unwrap because the argument has a default value.
+    let format = OutputFormat::from_str(sub_sub_args.value_of("format").unwrap())?;
+    if format != OutputFormat::Table {
+        let distance: Option<DistanceFn> = if sub_sub_args.is_present("latitude") {
+            Some(&distance)
+        } else {
+            None
+        };
+        let last_data = if show_last_data {
+            Some(&last_data)
+        } else {
+            None
+        };
+
+        return print_sites(
+            format,
+            sites_iter(),
+            &dl_db,
+            all_lists,
+            &registry,
+            distance,
+            last_data,
+        );
+    }
+
+    if sites_iter().count() == 0 {
         println!("No sites matched criteria.");
         return Ok(());
-    } else if sub_sub_args.is_present("latitude") {
-        TablePrinter::new()
-            .with_title("Sites".to_owned())
-            .with_column::<String, String>("Stn Num".to_owned(), &[])
-            .with_column::<String, String>("IDs".to_owned(), &[])
-            .with_column::<String, String>("STATE".to_owned(), &[])
-            .with_column::<String, String>("NAME".to_owned(), &[])
-            .with_column::<String, String>("UTC Offset".to_owned(), &[])
-            .with_column::<String, String>("Auto Download".to_owned(), &[])
-            .with_column::<String, String>("MODELS".to_owned(), &[])
-            .with_column::<String, String>("NOTES".to_owned(), &[])
-            .with_column::<String, String>("Coords".to_owned(), &[])
-            .with_column::<String, f64>("Distance (mi)".to_owned(), &[])
-            .with_column::<String, String>("Num files".to_owned(), &[])
-    } else {
-        TablePrinter::new()
-            .with_title("Sites".to_owned())
-            .with_column::<String, String>("Stn Num".to_owned(), &[])
-            .with_column::<String, String>("IDs".to_owned(), &[])
-            .with_column::<String, String>("STATE".to_owned(), &[])
-            .with_column::<String, String>("NAME".to_owned(), &[])
-            .with_column::<String, String>("UTC Offset".to_owned(), &[])
-            .with_column::<String, String>("Auto Download".to_owned(), &[])
-            .with_column::<String, String>("MODELS".to_owned(), &[])
-            .with_column::<String, String>("NOTES".to_owned(), &[])
-            .with_column::<String, String>("Coords".to_owned(), &[])
-            .with_column::<String, String>("Num files".to_owned(), &[])
-    };
+    }
+
+    let mut table_printer = TablePrinter::new()
+        .with_title("Sites".to_owned())
+        .with_column::<String, String>("Stn Num".to_owned(), &[])
+        .with_column::<String, String>("IDs".to_owned(), &[])
+        .with_column::<String, String>("STATE".to_owned(), &[])
+        .with_column::<String, String>("NAME".to_owned(), &[])
+        .with_column::<String, String>("Time Zone".to_owned(), &[])
+        .with_column::<String, String>("Auto Download".to_owned(), &[])
+        .with_column::<String, String>("MODELS".to_owned(), &[])
+        .with_column::<String, String>("NOTES".to_owned(), &[])
+        .with_column::<String, String>("Coords".to_owned(), &[]);
+    if sub_sub_args.is_present("latitude") {
+        table_printer =
+            table_printer.with_column::<String, f64>("Distance (mi)".to_owned(), &[]);
+    }
+    if show_last_data {
+        table_printer = table_printer
+            .with_column::<String, String>("Last Data".to_owned(), &[])
+            .with_column::<String, String>("Hours Since".to_owned(), &[])
+            .with_column::<String, String>("Stale".to_owned(), &[]);
+    }
+    table_printer = table_printer.with_column::<String, String>("Num files".to_owned(), &[]);
 
     let blank = "-".to_owned();
 
     for site in sites_iter() {
         let station_num = site.station_num;
         let ids = site.ids_as_string();
-        let state = site.state.map(|st| st.as_static_str()).unwrap_or(&"-");
+        let state = site.state.map(|st| st.as_static_str()).unwrap_or("-");
         let name = site.name.as_ref().unwrap_or(&blank);
-        let offset = site
-            .time_zone
-            .map(|val| val.to_string())
-            .unwrap_or_else(|| blank.clone());
-        let notes = site.notes.as_ref().unwrap_or(&blank);
-        let auto_dl = if dl_db.is_auto_downloaded(site.station_num)? {
-            "Yes"
-        } else {
-            "No"
+        let offset = match registry.time_zone_for(site.station_num)? {
+            Some(zone) => zone.name().to_owned(),
+            None => site
+                .time_zone
+                .map(|val| val.to_string())
+                .unwrap_or_else(|| blank.clone()),
         };
+        let notes = site.notes.as_ref().unwrap_or(&blank);
+        let auto_dl = auto_download_description(&dl_db, site.station_num, all_lists)?;
         let models = site.models_as_string();
         let num_files = site.number_of_files;
         let coords = site.coords_as_string();
-        let row = if sub_sub_args.is_present("latitude") {
-            let d = format!("{:.2}", distance(&site.coords[0]));
-            vec![
-                station_num.to_string(),
-                ids.to_string(),
-                state.to_string(),
-                name.to_string(),
-                offset,
-                auto_dl.to_string(),
-                models.to_string(),
-                notes.to_string(),
-                coords.to_string(),
-                d,
-                num_files.to_string(),
-            ]
-        } else {
-            vec![
-                station_num.to_string(),
-                ids.to_string(),
-                state.to_string(),
-                name.to_string(),
-                offset,
-                auto_dl.to_string(),
-                models.to_string(),
-                notes.to_string(),
-                coords.to_string(),
-                num_files.to_string(),
-            ]
-        };
+
+        let mut row = vec![
+            station_num.to_string(),
+            ids.to_string(),
+            state.to_string(),
+            name.to_string(),
+            offset,
+            auto_dl,
+            models.to_string(),
+            notes.to_string(),
+            coords.to_string(),
+        ];
+        if sub_sub_args.is_present("latitude") {
+            row.push(format!("{:.2}", distance(&site.coords[0])));
+        }
+        if show_last_data {
+            // Every site has an entry when the columns are shown.
+            let data = &last_data[&station_num];
+            let runs = data
+                .runs
+                .iter()
+                .map(|(model, init_time)| {
+                    format!("{} {}", model.as_static_str(), init_time.format("%Y-%m-%d %HZ"))
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            row.push(if runs.is_empty() { blank.clone() } else { runs });
+            row.push(
+                data.hours_since
+                    .map(|hours| hours.to_string())
+                    .unwrap_or_else(|| blank.clone()),
+            );
+            row.push(
+                match data.stale {
+                    Some(true) => "STALE",
+                    Some(false) => "",
+                    None => "-",
+                }
+                .to_owned(),
+            );
+        }
+        row.push(num_files.to_string());
+
         table_printer.add_row(row);
     }
 
@@ -265,198 +492,2324 @@ fn sites_list(
     Ok(())
 }
 
+/// Compare optional values, with the missing values last whether or not the order is reversed.
+fn cmp_missing_last<T: Ord>(
+    left: Option<T>,
+    right: Option<T>,
+    reverse: bool,
+) -> std::cmp::Ordering {
+    match (left, right) {
+        (Some(left), Some(right)) if reverse => right.cmp(&left),
+        (Some(left), Some(right)) => left.cmp(&right),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    }
+}
+
+/// Hours allowed for a model run to be downloaded after the next run is due, before an auto
+/// downloaded site counts as stale.
+const STALE_GRACE_HOURS: i64 = 6;
+
+/// The newest data for a site in the archive.
+struct LastData {
+    /// The newest init time for each model.
+    runs: BTreeMap<Model, NaiveDateTime>,
+    /// Hours since the newest init time of any model.
+    hours_since: Option<i64>,
+    /// Whether a model that is automatically downloaded has missed more than one run, `None` if
+    /// the site isn't automatically downloaded right now on any of the lists checked.
+    stale: Option<bool>,
+}
+
+fn site_last_data(
+    site: &StationSummary,
+    latest: &HashMap<(StationNumber, Model), NaiveDateTime>,
+    dl_db: &AutoDownloadListDb,
+    all_lists: bool,
+    now: NaiveDateTime,
+) -> Result<LastData, Box<dyn Error>> {
+    let runs: BTreeMap<Model, NaiveDateTime> = Model::iter()
+        .filter_map(|model| {
+            latest
+                .get(&(site.station_num, model))
+                .map(|&init_time| (model, init_time))
+        })
+        .collect();
+
+    let hours_since = runs
+        .values()
+        .max()
+        .map(|&newest| (now - newest).num_hours());
+
+    let entries = auto_download_entries(dl_db, site.station_num, all_lists)?;
+    let stale = is_stale(&entries, &runs, now);
+
+    Ok(LastData {
+        runs,
+        hours_since,
+        stale,
+    })
+}
+
+/// Whether a site is behind schedule for any of its active auto download entries, `None` if none
+/// are active.
+///
+/// A model is behind when its newest run is more than two runs plus `STALE_GRACE_HOURS` old.
+/// Models the site has never had data for are skipped, e.g. a model listed for a site outside its
+/// domain, unless the site has no data for any of the models it downloads. Then nothing is being
+/// downloaded and the site is stale.
+fn is_stale(
+    entries: &[AutoDownloadEntry],
+    runs: &BTreeMap<Model, NaiveDateTime>,
+    now: NaiveDateTime,
+) -> Option<bool> {
+    let mut stale = None;
+    for entry in entries.iter().filter(|entry| entry.is_active(now.date())) {
+        // Without a list of models, every model with data is downloaded.
+        let models: Vec<Model> = entry.models.clone().unwrap_or_else(|| Model::iter().collect());
+
+        let mut with_data = models
+            .iter()
+            .filter_map(|model| runs.get(model).map(|init_time| (model, init_time)))
+            .peekable();
+
+        let entry_stale = with_data.peek().is_none()
+            || with_data.any(|(model, &init_time)| {
+                let allowed = 2 * model.hours_between_runs() + STALE_GRACE_HOURS;
+                (now - init_time).num_hours() > allowed
+            });
+
+        stale = Some(stale == Some(true) || entry_stale);
+    }
+
+    stale
+}
+
+/// Distance in miles from the point given to `sites list`.
+type DistanceFn<'a> = &'a dyn Fn(&(f64, f64)) -> f64;
+
+/// A site as printed by `sites list` in the machine readable formats.
+#[derive(Serialize)]
+struct SiteRecord {
+    station_num: u32,
+    ids: Vec<String>,
+    state: Option<&'static str>,
+    name: Option<String>,
+    utc_offset: Option<String>,
+    /// The IANA time zone, if one is set.
+    time_zone: Option<&'static str>,
+    auto_download: bool,
+    /// The auto download lists the site is on.
+    auto_download_lists: Vec<AutoDownloadRecord>,
+    models: Vec<&'static str>,
+    notes: Option<String>,
+    coords: Vec<CoordsRecord>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    distance_mi: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_data: Option<LastDataRecord>,
+    num_files: u32,
+}
+
+#[derive(Serialize)]
+struct AutoDownloadRecord {
+    list: String,
+    models: Option<Vec<&'static str>>,
+    window: Option<String>,
+    active: bool,
+}
+
+#[derive(Serialize)]
+struct LastDataRecord {
+    /// The newest init time for each model.
+    last_init_times: BTreeMap<&'static str, String>,
+    hours_since_last_data: Option<i64>,
+    /// Only for sites that are automatically downloaded right now.
+    stale: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct CoordsRecord {
+    lat: f64,
+    lon: f64,
+}
+
+/// Print the sites in one of the machine readable formats.
+fn print_sites<'a>(
+    format: OutputFormat,
+    sites: impl Iterator<Item = &'a StationSummary>,
+    dl_db: &AutoDownloadListDb,
+    all_lists: bool,
+    registry: &SiteRegistryDb,
+    distance: Option<DistanceFn>,
+    last_data: Option<&HashMap<StationNumber, LastData>>,
+) -> Result<(), Box<dyn Error>> {
+    let today = Utc::now().date_naive();
+
+    let mut records = vec![];
+    for site in sites {
+        let auto_download_lists: Vec<AutoDownloadRecord> =
+            auto_download_entries(dl_db, site.station_num, all_lists)?
+                .into_iter()
+                .map(|entry| AutoDownloadRecord {
+                    active: entry.is_active(today),
+                    models: entry
+                        .models
+                        .map(|models| models.iter().map(|m| m.as_static_str()).collect()),
+                    window: entry.window.map(|w| w.to_string()),
+                    list: entry.list,
+                })
+                .collect();
+
+        records.push(SiteRecord {
+            station_num: site.station_num.into(),
+            ids: site.ids.clone(),
+            state: site.state.map(|st| st.as_static_str()),
+            name: site.name.clone(),
+            utc_offset: site.time_zone.map(|tz| tz.to_string()),
+            time_zone: registry.time_zone_for(site.station_num)?.map(|zone| zone.name()),
+            auto_download: !auto_download_lists.is_empty(),
+            auto_download_lists,
+            models: site.models.iter().map(|m| m.as_static_str()).collect(),
+            notes: site.notes.clone(),
+            coords: site
+                .coords
+                .iter()
+                .map(|&(lat, lon)| CoordsRecord { lat, lon })
+                .collect(),
+            distance_mi: distance.and_then(|distance| site.coords.first().map(distance)),
+            last_data: last_data
+                .and_then(|last_data| last_data.get(&site.station_num))
+                .map(|data| LastDataRecord {
+                    last_init_times: data
+                        .runs
+                        .iter()
+                        .map(|(model, init_time)| {
+                            let init_time = init_time.format(MACHINE_TIME_FORMAT).to_string();
+                            (model.as_static_str(), init_time)
+                        })
+                        .collect(),
+                    hours_since_last_data: data.hours_since,
+                    stale: data.stale,
+                }),
+            num_files: site.number_of_files,
+        });
+    }
+
+    if format == OutputFormat::Json {
+        return OutputFormat::write_json(&records);
+    }
+
+    let mut header = vec![
+        "station_num",
+        "ids",
+        "state",
+        "name",
+        "utc_offset",
+        "time_zone",
+        "auto_download",
+        "auto_download_lists",
+        "auto_download_models",
+        "auto_download_window",
+        "auto_download_active",
+        "models",
+        "notes",
+        "latitude",
+        "longitude",
+    ];
+    if distance.is_some() {
+        header.push("distance_mi");
+    }
+    if last_data.is_some() {
+        header.extend(["last_init_times", "hours_since_last_data", "stale"]);
+    }
+    header.push("num_files");
+
+    // Lists are separated by semicolons, sites with more than one location have a latitude and
+    // longitude for each. Likewise the auto download models, window, and active columns have a
+    // value for each auto download list, with the models separated by commas.
+    let rows: Vec<Vec<String>> = records
+        .into_iter()
+        .map(|rec| {
+            let mut row = vec![
+                rec.station_num.to_string(),
+                rec.ids.join(";"),
+                rec.state.unwrap_or_default().to_owned(),
+                rec.name.unwrap_or_default(),
+                rec.utc_offset.unwrap_or_default(),
+                rec.time_zone.unwrap_or_default().to_owned(),
+                rec.auto_download.to_string(),
+                rec.auto_download_lists
+                    .iter()
+                    .map(|dl| dl.list.clone())
+                    .collect::<Vec<_>>()
+                    .join(";"),
+                rec.auto_download_lists
+                    .iter()
+                    .map(|dl| dl.models.as_ref().map(|m| m.join(",")).unwrap_or_default())
+                    .collect::<Vec<_>>()
+                    .join(";"),
+                rec.auto_download_lists
+                    .iter()
+                    .map(|dl| dl.window.clone().unwrap_or_default())
+                    .collect::<Vec<_>>()
+                    .join(";"),
+                rec.auto_download_lists
+                    .iter()
+                    .map(|dl| dl.active.to_string())
+                    .collect::<Vec<_>>()
+                    .join(";"),
+                rec.models.join(";"),
+                rec.notes.unwrap_or_default(),
+                rec.coords
+                    .iter()
+                    .map(|c| c.lat.to_string())
+                    .collect::<Vec<_>>()
+                    .join(";"),
+                rec.coords
+                    .iter()
+                    .map(|c| c.lon.to_string())
+                    .collect::<Vec<_>>()
+                    .join(";"),
+            ];
+            if let Some(distance) = rec.distance_mi {
+                row.push(format!("{:.2}", distance));
+            }
+            if let Some(data) = rec.last_data {
+                row.push(
+                    data.last_init_times
+                        .iter()
+                        .map(|(model, init_time)| format!("{}={}", model, init_time))
+                        .collect::<Vec<_>>()
+                        .join(";"),
+                );
+                row.push(data.hours_since_last_data.map(|h| h.to_string()).unwrap_or_default());
+                row.push(data.stale.map(|stale| stale.to_string()).unwrap_or_default());
+            }
+            row.push(rec.num_files.to_string());
+            row
+        })
+        .collect();
+
+    format.write_delimited(&header, &rows)
+}
+
 fn sites_modify(
-    root: &PathBuf,
+    root: &Path,
     _sub_args: &ArgMatches,
     sub_sub_args: &ArgMatches,
 ) -> Result<(), Box<dyn Error>> {
-    let arch = &Archive::connect(root)?;
+    let arch = &Archive::connect(&root)?;
 
-    let site = {
-        // Safe to unwrap because the argument is required.
-        let str_val = sub_sub_args.value_of("stn").unwrap();
+    //
+    // Parse all the changes before touching anything.
+    //
+    let new_state = sub_sub_args.value_of("state").map(|new_state| {
+        StateProv::from_str(&new_state.to_uppercase())
+            .unwrap_or_else(|_| bail(&format!("Unable to parse state/providence: {}", new_state)))
+    });
 
-        if let Ok(stn_num) = str_val.parse::<u32>().map(StationNumber::from) {
-            stn_num
+    let new_name = sub_sub_args.value_of("name");
+    let new_notes = sub_sub_args.value_of("notes");
+
+    // None for no change, Some(None) to remove the zone.
+    let new_zone: Option<Option<Tz>> = sub_sub_args.value_of("time-zone").map(|zone| {
+        if zone.eq_ignore_ascii_case("none") {
+            None
         } else {
-            bfkmd::site_id_to_station_num(arch, str_val)?
+            Some(parse_time_zone(zone))
         }
-    };
+    });
 
-    let mut site = arch
-        .site(site)
-        .ok_or_else(|| BufkitDataErr::GeneralError(format!("Invalid Site: {}", site)))?;
+    // Setting a zone also sets the fixed offset to its standard time offset.
+    let new_offset = sub_sub_args
+        .value_of("utc-offset")
+        .map(|new_offset| {
+            let seconds = match new_offset.parse::<i32>() {
+                Ok(hours) => hours * 3600,
+                Err(_) => bail(&format!("Invalid UTC offset: {}", new_offset)),
+            };
+
+            FixedOffset::east_opt(seconds)
+                .unwrap_or_else(|| bail(&format!("Invalid UTC offset: {}", new_offset)))
+        })
+        .or_else(|| new_zone.flatten().map(bfkmd::standard_offset));
 
-    let mut update_in_archive_needed = false;
+    let dl_list = sub_sub_args
+        .value_of("list")
+        .unwrap_or(AutoDownloadListDb::DEFAULT_LIST);
+    let dl_db = AutoDownloadListDb::open_or_create(root)?.with_list(dl_list);
+    let registry = SiteRegistryDb::open_or_create(root)?;
 
-    if let Some(new_state) = sub_sub_args.value_of("state") {
-        match StateProv::from_str(&new_state.to_uppercase()) {
-            Ok(new_state) => {
-                site.state = Some(new_state);
-                update_in_archive_needed = true;
+    let auto_download = sub_sub_args.value_of("auto-download").map(|dl| match dl {
+        "Yes" | "yes" => true,
+        "No" | "no" => false,
+        _ => unreachable!(),
+    });
+
+    // None for no change, Some(None) for all models.
+    let dl_models: Option<Option<Vec<Model>>> =
+        sub_sub_args.values_of("auto-download-models").map(|model_strs| {
+            let model_strs: Vec<&str> = model_strs.collect();
+            if model_strs.iter().any(|m| m.eq_ignore_ascii_case("all")) {
+                None
+            } else {
+                let mut models = Vec::with_capacity(model_strs.len());
+                for model in model_strs {
+                    match Model::from_str(model) {
+                        Ok(model) => models.push(model),
+                        Err(_) => bail(&format!("Model {} does not exist!", model)),
+                    }
+                }
+                models.sort_unstable();
+                models.dedup();
+
+                Some(models)
             }
-            Err(_) => println!("Unable to parse state/providence: {}", new_state),
-        }
-    }
+        });
+
+    // None for no change, Some(None) to always download.
+    let dl_window: Option<Option<DownloadWindow>> = match sub_sub_args
+        .values_of("auto-download-window")
+        .map(|window_strs| window_strs.collect::<Vec<&str>>())
+        .as_deref()
+    {
+        None => None,
+        Some([always]) if always.eq_ignore_ascii_case("always") => Some(None),
+        Some([start, end]) => Some(Some(DownloadWindow::parse(start, end)?)),
+        Some(_) => bail("The download window needs a START and an END, or 'always'."),
+    };
 
-    if let Some(dl) = sub_sub_args.value_of("auto-download") {
-        let dl_db = bfkmd::AutoDownloadListDb::open_or_create(root)?;
+    // None for no change, Some(None) to go back to the coordinates in the files.
+    let new_coords: Option<Option<(f64, f64)>> = sub_sub_args.value_of("coords").map(|coords| {
+        if coords.eq_ignore_ascii_case("none") {
+            return None;
+        }
 
-        match dl {
-            "Yes" | "yes" => dl_db.add_site(site.station_num)?,
-            "No" | "no" => dl_db.remove_site(site.station_num)?,
-            _ => unreachable!(),
+        match coords
+            .split(',')
+            .map(|val| f64::from_str(val.trim()))
+            .collect::<Result<Vec<f64>, _>>()
+            .as_deref()
+        {
+            Ok(&[lat, lon]) if (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon) => {
+                Some((lat, lon))
+            }
+            _ => bail(&format!(
+                "Invalid coordinates, use LAT,LON with the longitude from -180 to 180: {}",
+                coords
+            )),
         }
-    }
+    });
 
-    if let Some(new_name) = sub_sub_args.value_of("name") {
-        site.name = Some(new_name.to_owned());
-        update_in_archive_needed = true;
+    let new_elevation = sub_sub_args.value_of("elevation").map(|elevation| {
+        f64::from_str(elevation)
+            .unwrap_or_else(|_| bail(&format!("Unable to parse elevation: {}", elevation)))
+    });
+
+    let add_ids: Vec<(Model, String)> = sub_sub_args
+        .values_of("add-id")
+        .into_iter()
+        .flatten()
+        .map(parse_id_arg)
+        .collect();
+    let retire_ids: Vec<(Model, String)> = sub_sub_args
+        .values_of("retire-id")
+        .into_iter()
+        .flatten()
+        .map(parse_id_arg)
+        .collect();
+
+    //
+    // Describe the changes so they can be checked before they are made.
+    //
+    let mut changes = vec![];
+    if let Some(new_name) = new_name {
+        changes.push(format!("name to '{}'", new_name));
+    }
+    if let Some(new_state) = new_state {
+        changes.push(format!("state to {}", new_state.as_static_str()));
+    }
+    if let Some(new_zone) = new_zone {
+        let zone = new_zone.map(|zone| zone.name()).unwrap_or("none");
+        changes.push(format!("time zone to {}", zone));
+    }
+    if let Some(new_offset) = new_offset {
+        changes.push(format!("UTC offset to {}", new_offset));
+    }
+    if let Some(new_notes) = new_notes {
+        changes.push(format!("notes to '{}'", new_notes));
+    }
+    if let Some(auto_download) = auto_download {
+        let action = if auto_download { "add to" } else { "remove from" };
+        changes.push(format!("{} auto download list '{}'", action, dl_list));
+    }
+    if let Some(ref models) = dl_models {
+        let models = models
+            .as_ref()
+            .map(|models| {
+                models
+                    .iter()
+                    .map(|m| m.as_static_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .unwrap_or_else(|| "all".to_owned());
+        changes.push(format!("auto download models to {} ('{}')", models, dl_list));
+    }
+    if let Some(window) = dl_window {
+        let window = window
+            .map(|window| window.to_string())
+            .unwrap_or_else(|| "always".to_owned());
+        changes.push(format!("auto download window to {} ('{}')", window, dl_list));
+    }
+    if let Some(coords) = new_coords {
+        let coords = coords
+            .map(|(lat, lon)| format!("{},{}", lat, lon))
+            .unwrap_or_else(|| "the ones in the files".to_owned());
+        changes.push(format!("coordinates to {}", coords));
+    }
+    if let Some(new_elevation) = new_elevation {
+        changes.push(format!("elevation to {} m", new_elevation));
+    }
+    for (model, id) in &add_ids {
+        changes.push(format!("add id {} for {}", id, model));
+    }
+    for (model, id) in &retire_ids {
+        changes.push(format!("retire id {} for {}", id, model));
     }
 
-    if let Some(new_notes) = sub_sub_args.value_of("notes") {
-        site.notes = Some(new_notes.to_owned());
-        update_in_archive_needed = true;
+    if changes.is_empty() {
+        bail("Nothing to change.");
     }
 
-    if let Some(new_offset) = sub_sub_args.value_of("utc-offset") {
-        if let Ok(new_offset) = new_offset.parse::<i32>() {
-            let seconds = new_offset * 3600;
-            if seconds < 0 {
-                site.time_zone = FixedOffset::west_opt(seconds.abs());
-            } else {
-                site.time_zone = FixedOffset::east_opt(seconds);
+    //
+    // Select the sites to change.
+    //
+    let single_site = sub_sub_args.value_of("stn");
+    let sites: Vec<SiteInfo> = match single_site {
+        Some(stn) => {
+            let site = parse_station_arg(arch, stn)?;
+            let site = arch
+                .site(site)
+                .ok_or_else(|| BufkitDataErr::GeneralError(format!("Invalid Site: {}", site)))?;
+
+            for (model, id) in &add_ids {
+                check_id_unused(arch, &registry, site.station_num, *model, id)?;
             }
-            update_in_archive_needed = true;
+
+            for (model, id) in &retire_ids {
+                let in_use = arch.ids(site.station_num, *model)?.contains(id)
+                    || registry.ids_for(site.station_num)?.contains(&(*model, id.clone()));
+                if !in_use {
+                    bail(&format!(
+                        "Site {} doesn't use the id {} for {}.",
+                        site.station_num, id, model
+                    ));
+                }
+            }
+
+            vec![site]
+        }
+        None => {
+            if new_name.is_some() {
+                bail("A name can only be set for one site at a time.");
+            }
+
+            if new_coords.is_some()
+                || new_elevation.is_some()
+                || !add_ids.is_empty()
+                || !retire_ids.is_empty()
+            {
+                bail("Coordinates, elevations, and ids can only be set for one site at a time.");
+            }
+
+            let sites = filtered_sites(arch, sub_sub_args)?;
+            if sites.is_empty() {
+                println!("No sites matched criteria.");
+                return Ok(());
+            }
+
+            sites
         }
+    };
+
+    let blank = || "-".to_owned();
+    let mut tp = TablePrinter::new()
+        .with_title(format!(
+            "Modify {} site{}",
+            sites.len(),
+            if sites.len() == 1 { "" } else { "s" }
+        ))
+        .with_header(format!("Set {}.", changes.join(", ")))
+        .with_column::<&str, String>("Stn Num", &[])
+        .with_column::<&str, String>("Name", &[])
+        .with_column::<&str, String>("State", &[])
+        .with_column::<&str, String>("UTC Offset", &[]);
+    for site in &sites {
+        tp.add_row(vec![
+            site.station_num.to_string(),
+            site.name.clone().unwrap_or_else(blank),
+            site.state
+                .map(|st| st.as_static_str().to_owned())
+                .unwrap_or_else(blank),
+            site.time_zone.map(|tz| tz.to_string()).unwrap_or_else(blank),
+        ]);
     }
+    tp.print()?;
 
-    if update_in_archive_needed {
-        arch.update_site(&site)?;
+    if sub_sub_args.is_present("dry-run") {
+        println!("Dry run, nothing was changed.");
+        return Ok(());
     }
-    Ok(())
-}
 
-fn sites_inventory(
-    root: &PathBuf,
-    _sub_args: &ArgMatches,
-    sub_sub_args: &ArgMatches,
-) -> Result<(), Box<dyn Error>> {
-    let arch = &Archive::connect(root)?;
+    // A single site named on the command line is changed without asking.
+    if single_site.is_none()
+        && !sub_sub_args.is_present("yes")
+        && !site_meta::confirm("Apply these changes? [y/N] ")?
+    {
+        println!("No changes were made.");
+        return Ok(());
+    }
 
-    let model = sub_sub_args.value_of("model").unwrap();
-    let model = match Model::from_str(model) {
-        Ok(model) => model,
-        Err(_) => {
-            bail(&format!("Model {} does not exist in the archive!", model));
+    //
+    // Apply the changes
+    //
+    for mut site in sites {
+        let mut update_in_archive_needed = false;
+
+        if let Some(new_state) = new_state {
+            site.state = Some(new_state);
+            update_in_archive_needed = true;
         }
-    };
 
-    let site = {
-        // Safe to unwrap because the argument is required.
-        let str_val = sub_sub_args.value_of("stn").unwrap();
+        if let Some(auto_download) = auto_download {
+            if auto_download {
+                dl_db.add_site(site.station_num)?;
+            } else {
+                dl_db.remove_site(site.station_num)?;
+            }
+        }
 
-        if let Ok(stn_num) = str_val.parse::<u32>().map(StationNumber::from) {
-            stn_num
-        } else {
-            arch.station_num_for_id(str_val, model)?
+        if let Some(ref models) = dl_models {
+            dl_db.set_models(site.station_num, models.as_deref())?;
         }
-    };
 
-    let site = arch
-        .site(site)
-        .ok_or_else(|| BufkitDataErr::GeneralError(format!("Invalid site: {}", site)))?;
+        if let Some(window) = dl_window {
+            dl_db.set_window(site.station_num, window)?;
+        }
 
-    let inv = match arch.inventory(site.station_num, model) {
-        ok @ Ok(_) => ok,
-        Err(BufkitDataErr::NotEnoughData) => {
-            bail(&format!(
-                "No data for model {} and site {}.",
-                model.as_static_str(),
-                site.description(),
-            ));
+        if let Some(new_name) = new_name {
+            site.name = Some(new_name.to_owned());
+            update_in_archive_needed = true;
         }
-        err @ Err(_) => err,
-    }?;
 
-    let (first, last) = match (inv.get(0), inv.iter().last()) {
-        (Some(first), Some(last)) => (first, last),
-        _ => unreachable!(),
-    };
+        if let Some(new_notes) = new_notes {
+            site.notes = Some(new_notes.to_owned());
+            update_in_archive_needed = true;
+        }
 
-    let missing = arch.missing_inventory(site.station_num, model, None)?;
+        if let Some(new_zone) = new_zone {
+            registry.set_time_zone(site.station_num, new_zone)?;
+        }
 
-    if missing.is_empty() {
-        println!("\nInventory for {} at {}.", model, site.description(),);
-        println!("   start: {}", first);
-        println!("     end: {}", last);
-        println!("          No missing runs!");
-    } else {
-        let mut tp = TablePrinter::new()
-            .with_title(format!(
-                "Inventory for {} at {}.",
-                model,
-                site.description(),
-            ))
-            .with_header(format!(
-                "{} -> {}",
-                first.format("%Y-%m-%d %H"),
-                last.format("%Y-%m-%d %H")
-            ));
+        if let Some(new_offset) = new_offset {
+            site.time_zone = Some(new_offset);
+            update_in_archive_needed = true;
+        }
 
-        let dl_db = AutoDownloadListDb::open_or_create(&arch.root())?;
-        let dl = if dl_db.is_auto_downloaded(site.station_num)? {
-            ""
-        } else {
-            " NOT"
-        };
-        tp = tp.with_footer(format!("This site is{} automatically downloaded.", dl));
+        for (model, id) in &retire_ids {
+            registry.retire_id(site.station_num, *model, id)?;
+        }
 
-        let mut cycles = vec![];
-        let mut start = vec![];
-        let mut end = vec![];
+        for (model, id) in &add_ids {
+            registry.add_id(site.station_num, *model, id)?;
+        }
 
-        let mut iter = missing.into_iter();
-        // Unwrap OK because we already check is_empty()
-        let mut start_run = iter.next().unwrap();
-        let mut end_run = start_run;
-        let mut last_round = start_run;
-        let mut total_missing = 0;
-        for missing in iter {
-            if (missing - last_round).num_hours() / model.hours_between_runs() == 1 {
-                end_run = missing;
-                last_round = missing;
-                continue;
-            } else {
-                let num_cycles = (end_run - start_run).num_hours() / model.hours_between_runs() + 1;
-                cycles.push(format!("{}", num_cycles));
-                start.push(format!("{}", start_run.format("%Y-%m-%d %H")));
-                end.push(format!("{}", end_run.format("%Y-%m-%d %H")));
-                total_missing += num_cycles;
-
-                start_run = missing;
-                end_run = missing;
-                last_round = missing;
+        if new_coords.is_some() || new_elevation.is_some() {
+            let registered = registry.coords_for(site.station_num)?;
+            let elevation_m = new_elevation.or_else(|| registered.and_then(|c| c.elevation_m));
+            let coords = match new_coords {
+                Some(coords) => coords,
+                None => registered.map(|c| (c.lat, c.lon)),
+            };
+
+            match (coords, new_elevation) {
+                (Some((lat, lon)), _) => registry.set_coords(
+                    site.station_num,
+                    RegisteredCoords {
+                        lat,
+                        lon,
+                        elevation_m,
+                    },
+                )?,
+                (None, None) => registry.remove_coords(site.station_num)?,
+                (None, Some(_)) => {
+                    // Keep the coordinates from the files along with the elevation.
+                    let (lat, lon) = arch
+                        .station_summaries()?
+                        .into_iter()
+                        .find(|summary| summary.station_num == site.station_num)
+                        .and_then(|summary| summary.coords.first().copied())
+                        .unwrap_or_else(|| {
+                            bail("The site has no coordinates, set them with --coords too.")
+                        });
+
+                    registry.set_coords(
+                        site.station_num,
+                        RegisteredCoords {
+                            lat,
+                            lon,
+                            elevation_m,
+                        },
+                    )?;
+                }
             }
         }
 
-        // Don't forget to add the last one!
-        let num_cycles = (end_run - start_run).num_hours() / model.hours_between_runs() + 1;
-        cycles.push(format!("{}", num_cycles));
-        start.push(format!("{}", start_run.format("%Y-%m-%d %H")));
-        end.push(format!("{}", end_run.format("%Y-%m-%d %H")));
-        total_missing += num_cycles;
+        if update_in_archive_needed {
+            arch.update_site(&site)?;
+        }
+    }
 
-        cycles.push(format!("- {} -", total_missing));
-        start.push(" -- Total -- ".to_owned());
-        end.push("".to_owned());
+    Ok(())
+}
+
+/// Select the sites matching the filters for a bulk change with `bkam sites modify`.
+fn filtered_sites(
+    arch: &Archive,
+    sub_sub_args: &ArgMatches,
+) -> Result<Vec<SiteInfo>, Box<dyn Error>> {
+    let states: Vec<StateProv> = sub_sub_args
+        .values_of("in-state")
+        .into_iter()
+        .flatten()
+        .map(|st| {
+            StateProv::from_str(&st.to_uppercase())
+                .unwrap_or_else(|_| bail(&format!("Unable to parse state/providence: {}", st)))
+        })
+        .collect();
+
+    let model = sub_sub_args.value_of("model").map(|model| {
+        Model::from_str(model).unwrap_or_else(|_| bail(&format!("Model {} does not exist!", model)))
+    });
+
+    let parse_f64 = |arg: &str| {
+        sub_sub_args.value_of(arg).map(|val| {
+            f64::from_str(val)
+                .unwrap_or_else(|_| bail(&format!("Unable to parse {}: {}", arg, val)))
+        })
+    };
+    let center = parse_f64("latitude").zip(parse_f64("longitude"));
+    let radius = parse_f64("radius");
+
+    let registry = SiteRegistryDb::open_or_create(arch.root())?;
+    let mut summaries = arch.station_summaries()?;
+    let registered = registered_summaries(arch, &summaries)?;
+    summaries.extend(registered);
+    apply_registry_corrections(arch, &registry, &mut summaries)?;
+
+    summaries.retain(|site| states.is_empty() || site.state.is_some_and(|st| states.contains(&st)));
+    summaries.retain(|site| !sub_sub_args.is_present("missing-state") || site.state.is_none());
+    summaries.retain(|site| model.is_none_or(|model| site.models.contains(&model)));
+    if let (Some(center), Some(radius)) = (center, radius) {
+        summaries.retain(|site| {
+            site.coords
+                .iter()
+                .any(|coords| haversine_miles(center, coords) <= radius)
+        });
+    }
+
+    summaries.sort_unstable_by_key(|site| Into::<u32>::into(site.station_num));
+
+    Ok(summaries
+        .into_iter()
+        .filter_map(|summary| arch.site(summary.station_num))
+        .collect())
+}
+
+fn sites_add(
+    root: &Path,
+    _sub_args: &ArgMatches,
+    sub_sub_args: &ArgMatches,
+) -> Result<(), Box<dyn Error>> {
+    let arch = &Archive::connect(&root)?;
+
+    // Safe to unwrap because the argument is required.
+    let stn_str = sub_sub_args.value_of("stn").unwrap();
+    let station_num = match stn_str.parse::<u32>() {
+        Ok(stn_num) if stn_num > 0 => StationNumber::from(stn_num),
+        _ => bail(&format!("Invalid station number: {}", stn_str)),
+    };
+
+    if arch.site(station_num).is_some() {
+        bail(&format!(
+            "Site {} is already in the archive, use 'bkam sites modify' to change it.",
+            station_num
+        ));
+    }
+
+    let registry = SiteRegistryDb::open_or_create(root)?;
+
+    // Safe to unwrap because the argument is required.
+    let mut ids: Vec<(Model, String)> = vec![];
+    for id_str in sub_sub_args.values_of("id").unwrap() {
+        let (model, id) = parse_id_arg(id_str);
+        check_id_unused(arch, &registry, station_num, model, &id)?;
+        ids.push((model, id));
+    }
+
+    let parse_f64 = |arg: &str| -> Option<f64> {
+        sub_sub_args.value_of(arg).map(|val| match f64::from_str(val) {
+            Ok(val) => val,
+            Err(_) => bail(&format!("Unable to parse {}: {}", arg, val)),
+        })
+    };
+
+    // Safe to unwrap because the arguments are required.
+    let lat = parse_f64("latitude").unwrap();
+    let lon = parse_f64("longitude").unwrap();
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+        bail(&format!(
+            "Invalid coordinates, the longitude must be from -180 to 180: ({}, {})",
+            lat, lon
+        ));
+    }
+    let elevation_m = parse_f64("elevation");
+
+    let state = sub_sub_args.value_of("state").map(|state| {
+        StateProv::from_str(&state.to_uppercase())
+            .unwrap_or_else(|_| bail(&format!("Unable to parse state/providence: {}", state)))
+    });
+
+    let zone = sub_sub_args.value_of("time-zone").map(parse_time_zone);
+
+    let time_zone = sub_sub_args
+        .value_of("utc-offset")
+        .map(|offset| {
+            match offset.parse::<i32>().ok().and_then(|hours| FixedOffset::east_opt(hours * 3600)) {
+                Some(time_zone) => time_zone,
+                None => bail(&format!("Invalid UTC offset: {}", offset)),
+            }
+        })
+        .or_else(|| zone.map(bfkmd::standard_offset));
+
+    arch.add_site(&SiteInfo {
+        station_num,
+        name: sub_sub_args.value_of("name").map(ToOwned::to_owned),
+        notes: sub_sub_args.value_of("notes").map(ToOwned::to_owned),
+        state,
+        time_zone,
+    })?;
+
+    for (model, id) in &ids {
+        registry.add_id(station_num, *model, id)?;
+    }
+    registry.set_coords(
+        station_num,
+        RegisteredCoords {
+            lat,
+            lon,
+            elevation_m,
+        },
+    )?;
+    if zone.is_some() {
+        registry.set_time_zone(station_num, zone)?;
+    }
+
+    if sub_sub_args.is_present("auto-download") {
+        let dl_list = sub_sub_args
+            .value_of("list")
+            .unwrap_or(AutoDownloadListDb::DEFAULT_LIST);
+        AutoDownloadListDb::open_or_create(root)?
+            .with_list(dl_list)
+            .add_site(station_num)?;
+    }
+
+    println!("Added site {}.", station_num);
+
+    Ok(())
+}
+
+/// Parse a MODEL=ID argument, the id is upper case.
+fn parse_id_arg(id_str: &str) -> (Model, String) {
+    let (model, id) = match id_str.split_once('=') {
+        Some((model, id)) if !id.trim().is_empty() => (model.trim(), id.trim()),
+        _ => bail(&format!("Invalid id, use MODEL=ID: {}", id_str)),
+    };
+
+    let model = match Model::from_str(model) {
+        Ok(model) => model,
+        Err(_) => bail(&format!("Model {} does not exist!", model)),
+    };
+
+    (model, id.to_uppercase())
+}
+
+/// Bail if another site already uses this id for the model.
+fn check_id_unused(
+    arch: &Archive,
+    registry: &SiteRegistryDb,
+    station_num: StationNumber,
+    model: Model,
+    id: &str,
+) -> Result<(), Box<dyn Error>> {
+    let other_stn = match arch.station_num_for_id(id, model) {
+        Ok(stn_num) => Some(stn_num),
+        Err(BufkitDataErr::NotInIndex) => registry.station_num_for_id(id, model)?,
+        Err(err) => return Err(err.into()),
+    };
+
+    if let Some(other_stn) = other_stn
+        && other_stn != station_num
+    {
+        bail(&format!(
+            "The id {} is already used by site {} for {}.",
+            id, other_stn, model
+        ));
+    }
+
+    Ok(())
+}
+
+fn sites_merge(
+    root: &Path,
+    _sub_args: &ArgMatches,
+    sub_sub_args: &ArgMatches,
+) -> Result<(), Box<dyn Error>> {
+    let arch = &Archive::connect(&root)?;
+
+    // Safe to unwrap because the arguments are required.
+    let old = parse_station_arg(arch, sub_sub_args.value_of("old").unwrap())?;
+    let new = parse_station_arg(arch, sub_sub_args.value_of("new").unwrap())?;
+    let dry_run = sub_sub_args.is_present("dry-run");
+
+    if old == new {
+        bail("Cannot merge a site into itself.");
+    }
+
+    let old_site = arch
+        .site(old)
+        .ok_or_else(|| BufkitDataErr::GeneralError(format!("Invalid Site: {}", old)))?;
+    let new_site = arch.site(new);
+
+    let merged_site = match new_site {
+        Some(ref new_site) => SiteInfo {
+            station_num: new,
+            name: new_site.name.clone().or_else(|| old_site.name.clone()),
+            notes: new_site.notes.clone().or_else(|| old_site.notes.clone()),
+            state: new_site.state.or(old_site.state),
+            time_zone: new_site.time_zone.or(old_site.time_zone),
+        },
+        None => SiteInfo {
+            station_num: new,
+            ..old_site.clone()
+        },
+    };
+
+    let index = ArchiveIndex::open(root)?;
+    let new_runs = index.runs_for(new)?;
+    let (duplicates, moving): (Vec<_>, Vec<_>) = index
+        .runs_for(old)?
+        .into_iter()
+        .partition(|run| new_runs.contains(run));
+
+    let dl_db = AutoDownloadListDb::open_or_create(root)?;
+    let dl_lists: Vec<String> = dl_db
+        .entries_for_site(old)?
+        .into_iter()
+        .map(|entry| entry.list)
+        .collect();
+
+    if new_site.is_some() {
+        println!("Merging site {} into site {}.", old, new);
+    } else {
+        println!("Renumbering site {} to {}.", old, new);
+    }
+
+    for model in Model::iter() {
+        let count = |runs: &[(Model, NaiveDateTime)]| runs.iter().filter(|r| r.0 == model).count();
+
+        let num_moving = count(&moving);
+        let num_duplicates = count(&duplicates);
+        if num_moving > 0 || num_duplicates > 0 {
+            println!(
+                "  {}: {} files to move, {} duplicate files to remove.",
+                model, num_moving, num_duplicates
+            );
+        }
+    }
+
+    let blank = "-".to_owned();
+    println!("  Name:       {}", merged_site.name.as_ref().unwrap_or(&blank));
+    println!(
+        "  State:      {}",
+        merged_site.state.map(|st| st.as_static_str()).unwrap_or("-")
+    );
+    println!("  Notes:      {}", merged_site.notes.as_ref().unwrap_or(&blank));
+    println!(
+        "  UTC Offset: {}",
+        merged_site
+            .time_zone
+            .map(|tz| tz.to_string())
+            .unwrap_or_else(|| blank.clone())
+    );
+    if !dl_lists.is_empty() {
+        println!("  Auto download lists: {}", dl_lists.join(", "));
+    }
+
+    if dry_run {
+        println!("Dry run, nothing was changed.");
+        return Ok(());
+    }
+
+    if new_site.is_some() {
+        arch.update_site(&merged_site)?;
+    } else {
+        arch.add_site(&merged_site)?;
+    }
+
+    for (model, init_time) in duplicates {
+        arch.remove(old, model, init_time)?;
+    }
+    index.renumber_files(old, new)?;
+
+    dl_db.move_site(old, new)?;
+    SiteRegistryDb::open_or_create(root)?.move_site(old, new)?;
+
+    // All the files were moved, so this only removes the site entry.
+    arch.remove_site(old)?;
+
+    println!("Done.");
+
+    Ok(())
+}
+
+fn sites_inventory(
+    root: &Path,
+    _sub_args: &ArgMatches,
+    sub_sub_args: &ArgMatches,
+) -> Result<(), Box<dyn Error>> {
+    let arch = &Archive::connect(&root)?;
+
+    // Empty for all the models.
+    let mut models: Vec<Model> = vec![];
+    for model in sub_sub_args.values_of("model").into_iter().flatten() {
+        if model.eq_ignore_ascii_case("all") {
+            models.clear();
+            break;
+        }
+
+        match Model::from_str(model) {
+            Ok(model) if !models.contains(&model) => models.push(model),
+            Ok(_) => {}
+            Err(_) => bail(&format!("Model {} does not exist in the archive!", model)),
+        }
+    }
+
+    if models.len() != 1 {
+        return compare_inventories(arch, sub_sub_args, models);
+    }
+    let model = models[0];
+
+    let site = {
+        // Safe to unwrap because the argument is required.
+        let str_val = sub_sub_args.value_of("stn").unwrap();
+
+        if let Ok(stn_num) = str_val.parse::<u32>().map(StationNumber::from) {
+            stn_num
+        } else {
+            match arch.station_num_for_id(str_val, model) {
+                Ok(stn_num) => stn_num,
+                Err(BufkitDataErr::NotInIndex) => bail(&format!(
+                    "No {} data for site {}.{}",
+                    model.as_static_str(),
+                    str_val,
+                    bfkmd::did_you_mean(arch, str_val)
+                )),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    };
+
+    let site = arch
+        .site(site)
+        .ok_or_else(|| BufkitDataErr::GeneralError(format!("Invalid site: {}", site)))?;
+
+    let inv = match arch.inventory(site.station_num, model) {
+        ok @ Ok(_) => ok,
+        Err(BufkitDataErr::NotEnoughData) => {
+            bail(&format!(
+                "No data for model {} and site {}.",
+                model.as_static_str(),
+                site.description(),
+            ));
+        }
+        err @ Err(_) => err,
+    }?;
+
+    let (first, last) = match (inv.first(), inv.iter().last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => unreachable!(),
+    };
+
+    let missing_runs = arch.missing_inventory(site.station_num, model, None)?;
+    let missing = missing_ranges(missing_runs.clone(), model);
+
+    let dl_db = AutoDownloadListDb::open_or_create(arch.root())?;
+    let entries = dl_db.entries_for_site(site.station_num)?;
+    let lists: Vec<&str> = entries
+        .iter()
+        .filter(|entry| entry.models.as_ref().is_none_or(|ms| ms.contains(&model)))
+        .map(|entry| entry.list.as_str())
+        .collect();
+
+    // Safe to unwrap because the argument has a default value.
+    let format = OutputFormat::from_str(sub_sub_args.value_of("format").unwrap())?;
+    match format {
+        OutputFormat::Table => {}
+        OutputFormat::Json => {
+            let time_str = |time: &NaiveDateTime| time.format(MACHINE_TIME_FORMAT).to_string();
+
+            return OutputFormat::write_json(&InventoryRecord {
+                station_num: site.station_num.into(),
+                name: site.name.clone(),
+                state: site.state.map(|st| st.as_static_str()),
+                model: model.as_static_str(),
+                first: time_str(first),
+                last: time_str(last),
+                auto_download_lists: lists,
+                total_missing: missing.iter().map(|range| range.cycles).sum(),
+                missing: missing
+                    .iter()
+                    .map(|range| MissingRangeRecord {
+                        start: time_str(&range.start),
+                        end: time_str(&range.end),
+                        cycles: range.cycles,
+                    })
+                    .collect(),
+                missing_runs: missing_runs.iter().map(time_str).collect(),
+            });
+        }
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let rows: Vec<Vec<String>> = missing
+                .iter()
+                .map(|range| {
+                    vec![
+                        range.start.format(MACHINE_TIME_FORMAT).to_string(),
+                        range.end.format(MACHINE_TIME_FORMAT).to_string(),
+                        range.cycles.to_string(),
+                    ]
+                })
+                .collect();
+
+            return format.write_delimited(&["start", "end", "cycles"], &rows);
+        }
+    }
+
+    if sub_sub_args.is_present("calendar") {
+        let title = format!("Inventory for {} at {}.", model, site.description());
+        return print_calendar(title, model, &inv, *first, *last);
+    }
+
+    let local_zone = LocalTimeZone::for_site(SiteRegistryDb::open(root)?.as_ref(), &site)?;
+    let local_time = |time: &NaiveDateTime| {
+        local_zone.map(|zone| zone.format_local(*time, LOCAL_TIME_FORMAT))
+    };
+
+    if missing.is_empty() {
+        println!("\nInventory for {} at {}.", model, site.description(),);
+        println!("   start: {}", first);
+        println!("     end: {}", last);
+        if let (Some(first), Some(last)) = (local_time(first), local_time(last)) {
+            println!("   local: {} -> {}", first, last);
+        }
+        println!("          No missing runs!");
+    } else {
+        let mut tp = TablePrinter::new()
+            .with_title(format!(
+                "Inventory for {} at {}.",
+                model,
+                site.description(),
+            ))
+            .with_header(format!(
+                "{} -> {}{}",
+                first.format("%Y-%m-%d %H"),
+                last.format("%Y-%m-%d %H"),
+                local_time(first)
+                    .zip(local_time(last))
+                    .map(|(first, last)| format!(" ({} -> {})", first, last))
+                    .unwrap_or_default()
+            ));
+
+        let footer = if entries.is_empty() {
+            "This site is NOT automatically downloaded.".to_owned()
+        } else if lists.is_empty() {
+            format!(
+                "This site is automatically downloaded, but NOT for {}.",
+                model
+            )
+        } else {
+            format!(
+                "This site is automatically downloaded (lists: {}).",
+                lists.join(", ")
+            )
+        };
+        tp = tp.with_footer(footer);
+
+        let mut cycles = vec![];
+        let mut start = vec![];
+        let mut end = vec![];
+        let mut local_start = vec![];
+        let mut local_end = vec![];
+        let mut total_missing = 0;
+
+        for range in missing {
+            cycles.push(format!("{}", range.cycles));
+            start.push(format!("{}", range.start.format("%Y-%m-%d %H")));
+            end.push(format!("{}", range.end.format("%Y-%m-%d %H")));
+            local_start.push(local_time(&range.start).unwrap_or_default());
+            local_end.push(local_time(&range.end).unwrap_or_default());
+            total_missing += range.cycles;
+        }
+
+        cycles.push(format!("- {} -", total_missing));
+        start.push(" -- Total -- ".to_owned());
+        end.push("".to_owned());
+        local_start.push("".to_owned());
+        local_end.push("".to_owned());
 
         tp = tp
             .with_column("Cycles", &cycles)
             .with_column("From", &start)
             .with_column("To", &end);
+        if local_zone.is_some() {
+            tp = tp
+                .with_column("Local From", &local_start)
+                .with_column("Local To", &local_end);
+        }
+        tp.print()?;
+    }
+
+    Ok(())
+}
+
+/// Compare the inventories for several models at a site side by side.
+fn compare_inventories(
+    arch: &Archive,
+    sub_sub_args: &ArgMatches,
+    mut models: Vec<Model>,
+) -> Result<(), Box<dyn Error>> {
+    if sub_sub_args.is_present("calendar") {
+        bail("The calendar view only works with a single model.");
+    }
+
+    // Safe to unwrap because the argument is required.
+    let station_num = parse_station_arg(arch, sub_sub_args.value_of("stn").unwrap())?;
+    let site = arch
+        .site(station_num)
+        .ok_or_else(|| BufkitDataErr::GeneralError(format!("Invalid site: {}", station_num)))?;
+
+    if models.is_empty() {
+        models = arch.models(station_num)?;
+    }
+    models.sort_unstable();
+
+    if models.is_empty() {
+        bail(&format!("No data for site {}.", site.description()));
+    }
+
+    let mut records: Vec<ModelInventoryRecord> = vec![];
+    let mut missing_dates: BTreeMap<NaiveDate, BTreeMap<Model, usize>> = BTreeMap::new();
+    for &model in &models {
+        let inv = match arch.inventory(station_num, model) {
+            Ok(inv) => inv,
+            Err(BufkitDataErr::NotEnoughData) => vec![],
+            Err(err) => return Err(err.into()),
+        };
+
+        let missing = if inv.is_empty() {
+            vec![]
+        } else {
+            arch.missing_inventory(station_num, model, None)?
+        };
+
+        for run in &missing {
+            *missing_dates
+                .entry(run.date())
+                .or_default()
+                .entry(model)
+                .or_default() += 1;
+        }
+
+        let expected = inv.len() + missing.len();
+        records.push(ModelInventoryRecord {
+            model: model.as_static_str(),
+            first: inv.first().copied(),
+            last: inv.last().copied(),
+            runs: inv.len(),
+            missing: missing.len(),
+            coverage: if expected > 0 {
+                Some(100.0 * inv.len() as f64 / expected as f64)
+            } else {
+                None
+            },
+        });
+    }
+
+    let missing_dates: Vec<MissingDateRecord> = missing_dates
+        .into_iter()
+        .map(|(date, models)| MissingDateRecord {
+            date: date.format("%Y-%m-%d").to_string(),
+            missing: models
+                .into_iter()
+                .map(|(model, cycles)| (model.as_static_str(), cycles))
+                .collect(),
+        })
+        .collect();
+
+    // Safe to unwrap because the argument has a default value.
+    let format = OutputFormat::from_str(sub_sub_args.value_of("format").unwrap())?;
+    match format {
+        OutputFormat::Table => {}
+        OutputFormat::Json => {
+            return OutputFormat::write_json(&InventoryComparisonRecord {
+                station_num: site.station_num.into(),
+                name: site.name.clone(),
+                state: site.state.map(|st| st.as_static_str()),
+                models: records,
+                missing_dates,
+            });
+        }
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let rows: Vec<Vec<String>> = records
+                .into_iter()
+                .map(|rec| {
+                    let time_str = |time: Option<NaiveDateTime>| {
+                        time.map(|time| time.format(MACHINE_TIME_FORMAT).to_string())
+                            .unwrap_or_default()
+                    };
+
+                    vec![
+                        rec.model.to_owned(),
+                        time_str(rec.first),
+                        time_str(rec.last),
+                        rec.runs.to_string(),
+                        rec.missing.to_string(),
+                        rec.coverage.map(|pct| format!("{:.1}", pct)).unwrap_or_default(),
+                    ]
+                })
+                .collect();
+
+            let header = ["model", "first", "last", "runs", "missing", "coverage"];
+            return format.write_delimited(&header, &rows);
+        }
+    }
+
+    let time_str = |time: Option<NaiveDateTime>| {
+        time.map(|time| time.format("%Y-%m-%d %H").to_string())
+            .unwrap_or_else(|| "-".to_owned())
+    };
+
+    let mut tp = TablePrinter::new()
+        .with_title(format!("Inventory for {}.", site.description()))
+        .with_column::<&str, String>("Model", &[])
+        .with_column::<&str, String>("First", &[])
+        .with_column::<&str, String>("Last", &[])
+        .with_column::<&str, String>("Runs", &[])
+        .with_column::<&str, String>("Missing", &[])
+        .with_column::<&str, String>("Coverage", &[]);
+    for rec in records {
+        tp.add_row(vec![
+            rec.model.to_uppercase(),
+            time_str(rec.first),
+            time_str(rec.last),
+            rec.runs.to_string(),
+            rec.missing.to_string(),
+            rec.coverage
+                .map(|pct| format!("{:.1}%", pct))
+                .unwrap_or_else(|| "-".to_owned()),
+        ]);
+    }
+    tp.print()?;
+
+    if missing_dates.is_empty() {
+        println!("No missing runs!");
+        return Ok(());
+    }
+
+    let mut tp = TablePrinter::new()
+        .with_title("Dates with missing runs".to_owned())
+        .with_column::<&str, String>("Date", &[])
+        .with_column::<&str, String>("Missing Cycles", &[]);
+    for rec in missing_dates {
+        let missing: Vec<String> = rec
+            .missing
+            .into_iter()
+            .map(|(model, cycles)| format!("{} ({})", model.to_uppercase(), cycles))
+            .collect();
+        tp.add_row(vec![rec.date, missing.join(", ")]);
+    }
+    tp.print()?;
+
+    Ok(())
+}
+
+/// Print the inventory as a grid with a row for each cycle of every month and a column for each
+/// day, so patterns like a cycle that is always missing stand out.
+fn print_calendar(
+    title: String,
+    model: Model,
+    inv: &[NaiveDateTime],
+    first: NaiveDateTime,
+    last: NaiveDateTime,
+) -> Result<(), Box<dyn Error>> {
+    const PRESENT: &str = "█";
+    const PARTIAL: &str = "▒";
+    const MISSING: &str = "·";
+
+    let step = model.hours_between_runs();
+    let mut cycles: Vec<u32> = (0..24 / step)
+        .map(|i| ((model.base_hour() + i * step) % 24) as u32)
+        .collect();
+    cycles.sort_unstable();
+
+    let present: HashSet<NaiveDateTime> = inv.iter().copied().collect();
+
+    let mut tp = TablePrinter::new()
+        .with_title(title)
+        .with_header(format!(
+            "{} -> {}",
+            first.format("%Y-%m-%d %H"),
+            last.format("%Y-%m-%d %H")
+        ))
+        .with_footer(format!(
+            "{} present  {} some cycles missing  {} missing",
+            PRESENT, PARTIAL, MISSING
+        ))
+        .with_column::<&str, String>("Month", &[])
+        .with_column::<&str, String>("Cycle", &[]);
+    for day in 1..=31 {
+        tp = tp.with_column::<String, String>(day.to_string(), &[]);
+    }
+
+    let mut month = first.date().with_day(1).unwrap();
+    while month <= last.date() {
+        let next_month = add_months(month, 1);
+        let days: Vec<NaiveDate> = month.iter_days().take_while(|&day| day < next_month).collect();
+
+        // None if the run isn't in the range of the inventory.
+        let status = |day: NaiveDate, hour: u32| {
+            let run = day.and_hms_opt(hour, 0, 0).unwrap();
+            if run < first || run > last {
+                None
+            } else {
+                Some(present.contains(&run))
+            }
+        };
+
+        let mut row = vec![month.format("%Y-%m").to_string(), "All".to_owned()];
+        row.extend(days.iter().map(|&day| {
+            let statuses: Vec<bool> = cycles.iter().filter_map(|&hr| status(day, hr)).collect();
+            if statuses.is_empty() {
+                ""
+            } else if statuses.iter().all(|&is_present| is_present) {
+                PRESENT
+            } else if statuses.iter().any(|&is_present| is_present) {
+                PARTIAL
+            } else {
+                MISSING
+            }
+            .to_owned()
+        }));
+        row.resize(33, String::new());
+        tp.add_row(row);
+
+        for &hour in &cycles {
+            let mut row = vec![String::new(), format!("{:02}Z", hour)];
+            row.extend(days.iter().map(|&day| {
+                match status(day, hour) {
+                    None => "",
+                    Some(true) => PRESENT,
+                    Some(false) => MISSING,
+                }
+                .to_owned()
+            }));
+            row.resize(33, String::new());
+            tp.add_row(row);
+        }
+
+        month = next_month;
+    }
+
+    tp.print()?;
+
+    Ok(())
+}
+
+fn sites_show(
+    root: &Path,
+    _sub_args: &ArgMatches,
+    sub_sub_args: &ArgMatches,
+) -> Result<(), Box<dyn Error>> {
+    let arch = Archive::connect(&root)?;
+    let index = ArchiveIndex::open(root)?;
+    let dl_db = AutoDownloadListDb::open_or_create(root)?;
+    let registry = SiteRegistryDb::open_or_create(root)?;
+    let missing_urls = MissingUrlDb::open_or_create_404_db(root)?;
+
+    // Safe to unwrap because the argument is required.
+    let station_num = parse_station_arg(&arch, sub_sub_args.value_of("stn").unwrap())?;
+    let site = arch
+        .site(station_num)
+        .ok_or_else(|| BufkitDataErr::GeneralError(format!("Invalid site: {}", station_num)))?;
+
+    let summary = arch
+        .station_summaries()?
+        .into_iter()
+        .find(|summary| summary.station_num == station_num);
+
+    //
+    // Metadata
+    //
+    let blank = || "-".to_owned();
+    let mut tp = TablePrinter::new()
+        .with_title(site.description())
+        .with_column::<&str, String>("", &[])
+        .with_column::<&str, String>("Value", &[]);
+    tp.add_row(vec!["Station Number".to_owned(), station_num.to_string()]);
+    tp.add_row(vec!["Name".to_owned(), site.name.clone().unwrap_or_else(blank)]);
+    tp.add_row(vec![
+        "State".to_owned(),
+        site.state.map(|st| st.as_static_str().to_owned()).unwrap_or_else(blank),
+    ]);
+    let time_zone = match registry.time_zone_for(station_num)? {
+        Some(zone) => format!(
+            "{} (standard time {})",
+            zone.name(),
+            bfkmd::standard_offset(zone)
+        ),
+        None => site.time_zone.map(|tz| tz.to_string()).unwrap_or_else(blank),
+    };
+    tp.add_row(vec!["Time Zone".to_owned(), time_zone]);
+    tp.add_row(vec!["Notes".to_owned(), site.notes.clone().unwrap_or_else(blank)]);
+    let entries = dl_db.entries_for_site(station_num)?;
+    let yes_no = if entries.is_empty() { "No" } else { "Yes" };
+    tp.add_row(vec!["Auto Download".to_owned(), yes_no.to_owned()]);
+    for entry in entries {
+        let models = entry
+            .models
+            .map(|models| {
+                models
+                    .iter()
+                    .map(|m| m.as_static_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .unwrap_or_else(|| "all models".to_owned());
+        let window = entry
+            .window
+            .map(|window| {
+                let today = Utc::now().date_naive();
+                let active = if window.is_active(today) {
+                    "active"
+                } else {
+                    "inactive"
+                };
+                format!(", {} [{}]", window, active)
+            })
+            .unwrap_or_default();
+
+        tp.add_row(vec![
+            format!("  List '{}'", entry.list),
+            format!("{}{}", models, window),
+        ]);
+    }
+    tp.print()?;
+
+    //
+    // Ids, from the files and registered with 'bkam sites add'
+    //
+    let mut ids: Vec<(Model, String, &str)> = vec![];
+    for model in Model::iter() {
+        for id in arch.ids(station_num, model)? {
+            ids.push((model, id, "files"));
+        }
+    }
+    for (model, id) in registry.ids_for(station_num)? {
+        if !ids.iter().any(|(m, i, _)| *m == model && *i == id) {
+            ids.push((model, id, "registered"));
+        }
+    }
+    for (model, id) in registry.retired_ids_for(station_num)? {
+        match ids.iter_mut().find(|(m, i, _)| *m == model && *i == id) {
+            Some(entry) => entry.2 = "retired",
+            None => ids.push((model, id, "retired")),
+        }
+    }
+    ids.sort_unstable();
+
+    if !ids.is_empty() {
+        let mut tp = TablePrinter::new()
+            .with_title("IDs".to_owned())
+            .with_column::<&str, String>("Model", &[])
+            .with_column::<&str, String>("ID", &[])
+            .with_column::<&str, String>("Source", &[]);
+        for (model, id, source) in &ids {
+            tp.add_row(vec![model.to_string(), id.clone(), source.to_string()]);
+        }
+        tp.print()?;
+    }
+
+    //
+    // Coordinates
+    //
+    let coords = summary
+        .as_ref()
+        .map(|summary| summary.coords.clone())
+        .unwrap_or_default();
+    let registered_coords = registry.coords_for(station_num)?;
+    // Coordinates registered for a site with files are a correction.
+    let registered_source = if coords.is_empty() {
+        "registered"
+    } else {
+        "corrected"
+    };
+    if !coords.is_empty() || registered_coords.is_some() {
+        let mut tp = TablePrinter::new()
+            .with_title("Coordinates".to_owned())
+            .with_column::<&str, String>("Latitude", &[])
+            .with_column::<&str, String>("Longitude", &[])
+            .with_column::<&str, String>("Elevation (m)", &[])
+            .with_column::<&str, String>("Source", &[]);
+        for (lat, lon) in coords {
+            tp.add_row(vec![lat.to_string(), lon.to_string(), blank(), "files".to_owned()]);
+        }
+        if let Some(RegisteredCoords {
+            lat,
+            lon,
+            elevation_m,
+        }) = registered_coords
+        {
+            tp.add_row(vec![
+                lat.to_string(),
+                lon.to_string(),
+                elevation_m.map(|elev| elev.to_string()).unwrap_or_else(blank),
+                registered_source.to_owned(),
+            ]);
+        }
+        tp.print()?;
+    }
+
+    //
+    // Model runs
+    //
+    let mut runs: BTreeMap<Model, (NaiveDateTime, NaiveDateTime, usize)> = BTreeMap::new();
+    for (model, init_time) in index.runs_for(station_num)? {
+        let entry = runs.entry(model).or_insert((init_time, init_time, 0));
+        entry.0 = entry.0.min(init_time);
+        entry.1 = entry.1.max(init_time);
+        entry.2 += 1;
+    }
+
+    if runs.is_empty() {
+        println!("No files in the archive for this site.");
+    } else {
+        let mut tp = TablePrinter::new()
+            .with_title("Model Runs".to_owned())
+            .with_column::<&str, String>("Model", &[])
+            .with_column::<&str, String>("First", &[])
+            .with_column::<&str, String>("Last", &[])
+            .with_column::<&str, String>("Files", &[]);
+        for (model, (first, last, num_files)) in runs {
+            tp.add_row(vec![
+                model.to_string(),
+                first.format("%Y-%m-%d %H").to_string(),
+                last.format("%Y-%m-%d %H").to_string(),
+                num_files.to_string(),
+            ]);
+        }
+        tp.print()?;
+    }
+
+    //
+    // URLs the downloader found missing
+    //
+    let mut site_ids: Vec<&str> = ids.iter().map(|(_, id, _)| id.as_str()).collect();
+    site_ids.sort_unstable();
+    site_ids.dedup();
+
+    let mut urls = vec![];
+    for id in site_ids {
+        urls.extend(missing_urls.urls_for_site(id)?);
+    }
+
+    if !urls.is_empty() {
+        let mut tp = TablePrinter::new()
+            .with_title("Missing URLs".to_owned())
+            .with_header("The downloader won't request these again.".to_owned())
+            .with_column::<&str, String>("URL", &[]);
+        for url in urls {
+            tp.add_row(vec![url]);
+        }
         tp.print()?;
     }
 
     Ok(())
 }
+
+fn sites_search(
+    root: &Path,
+    _sub_args: &ArgMatches,
+    sub_sub_args: &ArgMatches,
+) -> Result<(), Box<dyn Error>> {
+    let arch = Archive::connect(&root)?;
+
+    // Safe to unwrap because the argument is required.
+    let query = sub_sub_args.value_of("query").unwrap();
+
+    let limit: usize = sub_sub_args
+        .value_of("limit")
+        .map(|limit| {
+            limit
+                .parse()
+                .unwrap_or_else(|_| bail(&format!("Invalid limit: {}", limit)))
+        })
+        .unwrap_or(10);
+
+    let matches = bfkmd::search_sites(&arch, query)?;
+    if matches.is_empty() {
+        println!("No sites matched {}.", query);
+        return Ok(());
+    }
+
+    let blank = || "-".to_owned();
+    let mut tp = TablePrinter::new()
+        .with_title(format!("Sites matching '{}'", query))
+        .with_column::<&str, String>("Stn Num", &[])
+        .with_column::<&str, String>("IDs", &[])
+        .with_column::<&str, String>("Name", &[])
+        .with_column::<&str, String>("State", &[])
+        .with_column::<&str, String>("Matched", &[])
+        .with_column::<&str, String>("Score", &[]);
+    if matches.len() > limit {
+        tp = tp.with_footer(format!("{} more matches not shown.", matches.len() - limit));
+    }
+
+    for site in matches.into_iter().take(limit) {
+        tp.add_row(vec![
+            site.station_num.to_string(),
+            site.ids.join(", "),
+            site.name.unwrap_or_else(blank),
+            site.state
+                .map(|st| st.as_static_str().to_owned())
+                .unwrap_or_else(blank),
+            site.field.to_string(),
+            format!("{:.0}%", site.score * 100.0),
+        ]);
+    }
+    tp.print()?;
+
+    Ok(())
+}
+
+fn sites_migrate_time_zones(
+    root: &Path,
+    _sub_args: &ArgMatches,
+    sub_sub_args: &ArgMatches,
+) -> Result<(), Box<dyn Error>> {
+    let arch = Archive::connect(&root)?;
+    let registry = SiteRegistryDb::open_or_create(root)?;
+
+    let mut sites = arch.sites()?;
+    sites.sort_unstable_by_key(|site| Into::<u32>::into(site.station_num));
+
+    let mut zones: Vec<(StationNumber, Tz)> = vec![];
+    let mut num_unknown = 0;
+
+    let blank = || "-".to_owned();
+    let mut tp = TablePrinter::new()
+        .with_title("Time zones from UTC offsets".to_owned())
+        .with_column::<&str, String>("Stn Num", &[])
+        .with_column::<&str, String>("Name", &[])
+        .with_column::<&str, String>("State", &[])
+        .with_column::<&str, String>("UTC Offset", &[])
+        .with_column::<&str, String>("Time Zone", &[]);
+
+    for site in sites {
+        let offset = match site.time_zone {
+            Some(offset) => offset,
+            None => continue,
+        };
+
+        if registry.time_zone_for(site.station_num)?.is_some() {
+            continue;
+        }
+
+        let zone = bfkmd::guess_time_zone(site.state, offset);
+        match zone {
+            Some(zone) => zones.push((site.station_num, zone)),
+            None => num_unknown += 1,
+        }
+
+        tp.add_row(vec![
+            site.station_num.to_string(),
+            site.name.unwrap_or_else(blank),
+            site.state
+                .map(|st| st.as_static_str().to_owned())
+                .unwrap_or_else(blank),
+            offset.to_string(),
+            zone.map(|zone| zone.name().to_owned()).unwrap_or_else(|| "unknown".to_owned()),
+        ]);
+    }
+
+    if zones.is_empty() && num_unknown == 0 {
+        println!("Every site with a UTC offset already has a time zone.");
+        return Ok(());
+    }
+
+    if num_unknown > 0 {
+        tp = tp.with_footer(format!(
+            "No zone matches the state and offset of {} sites, set them with \
+             'bkam sites modify STN --time-zone ZONE'.",
+            num_unknown
+        ));
+    }
+    tp.print()?;
+
+    if zones.is_empty() || sub_sub_args.is_present("dry-run") {
+        println!("Nothing was changed.");
+        return Ok(());
+    }
+
+    if !sub_sub_args.is_present("yes")
+        && !site_meta::confirm(&format!("Set the time zone of {} sites? [y/N] ", zones.len()))?
+    {
+        println!("No changes were made.");
+        return Ok(());
+    }
+
+    for (station_num, zone) in zones {
+        registry.set_time_zone(station_num, Some(zone))?;
+    }
+
+    println!("Done.");
+
+    Ok(())
+}
+
+fn sites_autofill(
+    root: &Path,
+    _sub_args: &ArgMatches,
+    sub_sub_args: &ArgMatches,
+) -> Result<(), Box<dyn Error>> {
+    let arch = Archive::connect(&root)?;
+    let registry = SiteRegistryDb::open_or_create(root)?;
+
+    // Safe to unwrap because the argument has a default value.
+    let max_distance = sub_sub_args.value_of("max-distance").unwrap();
+    let max_distance = f64::from_str(max_distance)
+        .unwrap_or_else(|_| bail(&format!("Unable to parse max-distance: {}", max_distance)));
+
+    let mut summaries = arch.station_summaries()?;
+    let registered = registered_summaries(&arch, &summaries)?;
+    summaries.extend(registered);
+    apply_registry_corrections(&arch, &registry, &mut summaries)?;
+    summaries.sort_unstable_by_key(|site| Into::<u32>::into(site.station_num));
+
+    // The sites with their new values filled in, and the IANA zone to set.
+    let mut updates: Vec<(SiteInfo, Option<Tz>)> = vec![];
+    let mut num_unfilled = 0;
+
+    let blank = || "-".to_owned();
+    let mut tp = TablePrinter::new()
+        .with_title("Values filled in from the nearest station".to_owned())
+        .with_header(concat!(
+            "Values that are already set are kept and shown as '-'. The state is only filled in ",
+            "within 10 miles of a station, and the time zone within 50 miles, where no station in ",
+            "another state or time zone is nearly as close."
+        ).to_owned())
+        .with_column::<&str, String>("Stn Num", &[])
+        .with_column::<&str, String>("IDs", &[])
+        .with_column::<&str, String>("Nearest", &[])
+        .with_column::<&str, String>("State", &[])
+        .with_column::<&str, String>("Name", &[])
+        .with_column::<&str, String>("Time Zone", &[]);
+
+    for summary in summaries {
+        let mut site = match arch.site(summary.station_num) {
+            Some(site) => site,
+            None => continue,
+        };
+        let zone = registry.time_zone_for(site.station_num)?;
+
+        if site.state.is_some() && site.name.is_some() && site.time_zone.is_some() && zone.is_some()
+        {
+            continue;
+        }
+
+        let coords = summary.coords.first().copied();
+        let nearest = coords
+            .and_then(|(lat, lon)| bfkmd::nearest_known_station(lat, lon))
+            .filter(|(_, distance)| *distance <= max_distance);
+        let (coords, (stn, distance)) = match coords.zip(nearest) {
+            Some(found) => found,
+            None => {
+                num_unfilled += 1;
+                continue;
+            }
+        };
+
+        let new_state = site
+            .state
+            .is_none()
+            .then(|| bfkmd::known_station_state(coords.0, coords.1))
+            .flatten();
+
+        let new_name = site.name.is_none().then(|| {
+            if distance <= 5.0 {
+                stn.name.to_owned()
+            } else {
+                let direction = compass_direction((stn.lat, stn.lon), coords);
+                format!("{:.0} mi {} of {}", distance, direction, stn.name)
+            }
+        });
+
+        // A UTC offset set by hand has to match the standard time offset of the zone.
+        let station_zone = bfkmd::known_station_zone(coords.0, coords.1);
+        let new_zone = match (zone, site.time_zone, station_zone) {
+            (Some(_), _, _) => None,
+            (None, None, station_zone) => station_zone,
+            (None, Some(offset), Some(station_zone))
+                if bfkmd::standard_offset(station_zone) == offset =>
+            {
+                Some(station_zone)
+            }
+            (None, Some(offset), _) => bfkmd::guess_time_zone(site.state.or(new_state), offset),
+        };
+
+        let new_offset = match site.time_zone {
+            Some(_) => None,
+            None => new_zone.or(zone).map(bfkmd::standard_offset),
+        };
+
+        if new_state.is_none() && new_name.is_none() && new_zone.is_none() && new_offset.is_none()
+        {
+            num_unfilled += 1;
+            continue;
+        }
+
+        tp.add_row(vec![
+            site.station_num.to_string(),
+            summary.ids.join(","),
+            format!("{} ({:.0} mi)", stn.id, distance),
+            new_state
+                .map(|st| st.as_static_str().to_owned())
+                .unwrap_or_else(blank),
+            new_name.clone().unwrap_or_else(blank),
+            new_zone
+                .map(|zone| zone.name().to_owned())
+                .or_else(|| new_offset.map(|offset| offset.to_string()))
+                .unwrap_or_else(blank),
+        ]);
+
+        site.state = site.state.or(new_state);
+        site.name = site.name.or(new_name);
+        site.time_zone = site.time_zone.or(new_offset);
+        updates.push((site, new_zone));
+    }
+
+    if updates.is_empty() && num_unfilled == 0 {
+        println!("No sites are missing a state, name, or time zone.");
+        return Ok(());
+    }
+
+    if num_unfilled > 0 {
+        tp = tp.with_footer(format!(
+            "The missing values of {} sites could not be filled in, they have no coordinates, \
+             no station within {} miles, are near a state or time zone line, or have a UTC \
+             offset that doesn't match a time zone.",
+            num_unfilled, max_distance
+        ));
+    }
+    tp.print()?;
+
+    if updates.is_empty() || sub_sub_args.is_present("dry-run") {
+        println!("Nothing was changed.");
+        return Ok(());
+    }
+
+    if !sub_sub_args.is_present("yes")
+        && !site_meta::confirm(&format!("Fill in the values for {} sites? [y/N] ", updates.len()))?
+    {
+        println!("No changes were made.");
+        return Ok(());
+    }
+
+    for (site, zone) in updates {
+        arch.update_site(&site)?;
+        if let Some(zone) = zone {
+            registry.set_time_zone(site.station_num, Some(zone))?;
+        }
+    }
+
+    println!("Done.");
+
+    Ok(())
+}
+
+/// The compass direction, like NW, of the second point as seen from the first.
+fn compass_direction(from: (f64, f64), to: (f64, f64)) -> &'static str {
+    const DIRECTIONS: [&str; 8] = ["N", "NE", "E", "SE", "S", "SW", "W", "NW"];
+
+    let (lat1, lon1) = (from.0.to_radians(), from.1.to_radians());
+    let (lat2, lon2) = (to.0.to_radians(), to.1.to_radians());
+    let dlon = lon2 - lon1;
+
+    let y = dlon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * dlon.cos();
+    let bearing = y.atan2(x).to_degrees().rem_euclid(360.0);
+
+    DIRECTIONS[((bearing + 22.5) / 45.0) as usize % 8]
+}
+
+/// Parse an IANA time zone name like America/Denver.
+fn parse_time_zone(zone: &str) -> Tz {
+    Tz::from_str(zone).unwrap_or_else(|_| {
+        bail(&format!(
+            "Unknown time zone: {}, use an IANA name like America/Denver.",
+            zone
+        ))
+    })
+}
+
+/// Time format for local times, with the zone abbreviation.
+const LOCAL_TIME_FORMAT: &str = "%Y-%m-%d %H %Z";
+
+/// Time format for the machine readable outputs.
+const MACHINE_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// A run of consecutive missing model cycles.
+struct MissingRange {
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    cycles: i64,
+}
+
+/// Group the missing runs into ranges of consecutive cycles.
+fn missing_ranges(missing: Vec<NaiveDateTime>, model: Model) -> Vec<MissingRange> {
+    let mut ranges = vec![];
+
+    let mut iter = missing.into_iter();
+    let mut start_run = match iter.next() {
+        Some(run) => run,
+        None => return ranges,
+    };
+    let mut end_run = start_run;
+
+    let make_range = |start: NaiveDateTime, end: NaiveDateTime| MissingRange {
+        start,
+        end,
+        cycles: (end - start).num_hours() / model.hours_between_runs() + 1,
+    };
+
+    for missing in iter {
+        if (missing - end_run).num_hours() / model.hours_between_runs() == 1 {
+            end_run = missing;
+        } else {
+            ranges.push(make_range(start_run, end_run));
+            start_run = missing;
+            end_run = missing;
+        }
+    }
+
+    // Don't forget to add the last one!
+    ranges.push(make_range(start_run, end_run));
+
+    ranges
+}
+
+#[derive(Serialize)]
+struct InventoryRecord<'a> {
+    station_num: u32,
+    name: Option<String>,
+    state: Option<&'static str>,
+    model: &'static str,
+    first: String,
+    last: String,
+    auto_download_lists: Vec<&'a str>,
+    total_missing: i64,
+    missing: Vec<MissingRangeRecord>,
+    /// Every missing init time, not grouped into ranges.
+    missing_runs: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct InventoryComparisonRecord {
+    station_num: u32,
+    name: Option<String>,
+    state: Option<&'static str>,
+    models: Vec<ModelInventoryRecord>,
+    missing_dates: Vec<MissingDateRecord>,
+}
+
+#[derive(Serialize)]
+struct ModelInventoryRecord {
+    model: &'static str,
+    #[serde(serialize_with = "serialize_machine_time")]
+    first: Option<NaiveDateTime>,
+    #[serde(serialize_with = "serialize_machine_time")]
+    last: Option<NaiveDateTime>,
+    runs: usize,
+    missing: usize,
+    /// Percent of the runs from first to last that are in the archive.
+    coverage: Option<f64>,
+}
+
+fn serialize_machine_time<S: Serializer>(
+    time: &Option<NaiveDateTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match time {
+        Some(time) => serializer.serialize_some(&time.format(MACHINE_TIME_FORMAT).to_string()),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// A day with at least one missing run and the number of missing cycles for each model.
+#[derive(Serialize)]
+struct MissingDateRecord {
+    date: String,
+    missing: BTreeMap<&'static str, usize>,
+}
+
+#[derive(Serialize)]
+struct MissingRangeRecord {
+    start: String,
+    end: String,
+    cycles: i64,
+}
+
+/// Get a station number from a command line argument that may be a station number or an id.
+fn parse_station_arg(arch: &Archive, str_val: &str) -> Result<StationNumber, Box<dyn Error>> {
+    if let Ok(stn_num) = str_val.parse::<u32>().map(StationNumber::from) {
+        Ok(stn_num)
+    } else {
+        Ok(bfkmd::site_id_to_station_num(arch, str_val)?)
+    }
+}
+
+/// Build summaries for the sites registered with `bkam sites add` that have no files yet, these
+/// aren't included in the archive's station summaries.
+fn registered_summaries(
+    arch: &Archive,
+    known: &[StationSummary],
+) -> Result<Vec<StationSummary>, Box<dyn Error>> {
+    let registry = SiteRegistryDb::open_or_create(arch.root())?;
+
+    let mut summaries = vec![];
+    for station_num in registry.station_nums()? {
+        if known.iter().any(|summary| summary.station_num == station_num) {
+            continue;
+        }
+
+        let site = match arch.site(station_num) {
+            Some(site) => site,
+            None => continue,
+        };
+
+        let ids = registry.ids_for(station_num)?;
+        let mut models: Vec<Model> = ids.iter().map(|(model, _)| *model).collect();
+        models.dedup();
+        let mut ids: Vec<String> = ids.into_iter().map(|(_, id)| id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+
+        let coords = registry
+            .coords_for(station_num)?
+            .map(|coords| vec![(coords.lat, coords.lon)])
+            .unwrap_or_default();
+
+        summaries.push(StationSummary {
+            station_num,
+            ids,
+            models,
+            name: site.name,
+            notes: site.notes,
+            state: site.state,
+            time_zone: site.time_zone,
+            coords,
+            number_of_files: 0,
+        });
+    }
+
+    Ok(summaries)
+}
+
+/// Use the coordinates and ids registered with `bkam sites modify` instead of the ones from the
+/// files, and drop the retired ids.
+fn apply_registry_corrections(
+    arch: &Archive,
+    registry: &SiteRegistryDb,
+    summaries: &mut [StationSummary],
+) -> Result<(), Box<dyn Error>> {
+    for summary in summaries {
+        let station_num = summary.station_num;
+
+        if let Some(coords) = registry.coords_for(station_num)? {
+            summary.coords = vec![(coords.lat, coords.lon)];
+        }
+
+        // An id is only dropped once it is retired for every model that uses it.
+        let retired = registry.retired_ids_for(station_num)?;
+        if !retired.is_empty() {
+            let mut in_use: Vec<(Model, String)> = vec![];
+            for model in Model::iter() {
+                for id in arch.ids(station_num, model)? {
+                    if !retired.contains(&(model, id.clone())) {
+                        in_use.push((model, id));
+                    }
+                }
+            }
+
+            summary.ids.retain(|id| in_use.iter().any(|(_, used)| used == id));
+        }
+
+        for (_, id) in registry.ids_for(summary.station_num)? {
+            if !summary.ids.contains(&id) {
+                summary.ids.push(id);
+            }
+        }
+        summary.ids.sort_unstable();
+    }
+
+    Ok(())
+}
+
+/// The auto download entries for a site, on every list or only the selected one.
+fn auto_download_entries(
+    dl_db: &AutoDownloadListDb,
+    station_num: StationNumber,
+    all_lists: bool,
+) -> Result<Vec<AutoDownloadEntry>, BufkitDataErr> {
+    if all_lists {
+        dl_db.entries_for_site(station_num)
+    } else {
+        Ok(dl_db.entry_for(station_num)?.into_iter().collect())
+    }
+}
+
+/// Describe which lists a site is automatically downloaded on, for which models, and when.
+fn auto_download_description(
+    dl_db: &AutoDownloadListDb,
+    station_num: StationNumber,
+    all_lists: bool,
+) -> Result<String, Box<dyn Error>> {
+    let entries = auto_download_entries(dl_db, station_num, all_lists)?;
+    if entries.is_empty() {
+        return Ok("No".to_owned());
+    }
+
+    let today = Utc::now().date_naive();
+    let descriptions: Vec<String> = entries
+        .into_iter()
+        .map(|entry| {
+            let mut desc = entry.list;
+
+            if let Some(models) = entry.models {
+                let models: Vec<&str> = models.iter().map(|m| m.as_static_str()).collect();
+                desc.push_str(&format!(" ({})", models.join(", ")));
+            }
+
+            if let Some(window) = entry.window {
+                let active = if window.is_active(today) {
+                    "active"
+                } else {
+                    "inactive"
+                };
+                desc.push_str(&format!(" {} [{}]", window, active));
+            }
+
+            desc
+        })
+        .collect();
+
+    Ok(descriptions.join("; "))
+}
+
+#[cfg(test)]
+mod unit {
+    use super::*;
+
+    #[test]
+    fn test_is_stale() {
+        let time = |day: u32, hour: u32| {
+            NaiveDate::from_ymd_opt(2024, 5, day)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+        };
+        let entry = |models: Option<Vec<Model>>, window: Option<DownloadWindow>| {
+            AutoDownloadEntry {
+                list: AutoDownloadListDb::DEFAULT_LIST.to_owned(),
+                station_num: StationNumber::from(727730),
+                models,
+                window,
+            }
+        };
+
+        let now = time(10, 12);
+        let runs: BTreeMap<Model, NaiveDateTime> =
+            [(Model::GFS, time(10, 0)), (Model::NAM, time(9, 12))].into_iter().collect();
+
+        // Not on any list, or outside the download window.
+        assert_eq!(is_stale(&[], &runs, now), None);
+        let window = DownloadWindow::parse("06-01", "09-30").unwrap();
+        assert_eq!(is_stale(&[entry(None, Some(window))], &runs, now), None);
+
+        // The NAM is 24 hours old, more than two runs plus the grace hours.
+        assert_eq!(is_stale(&[entry(None, None)], &runs, now), Some(true));
+        assert_eq!(is_stale(&[entry(Some(vec![Model::GFS]), None)], &runs, now), Some(false));
+        assert_eq!(is_stale(&[entry(Some(vec![Model::NAM]), None)], &runs, now), Some(true));
+
+        // 18 hours old is still on time, 19 hours is not.
+        assert_eq!(is_stale(&[entry(None, None)], &runs, time(10, 6)), Some(false));
+        assert_eq!(is_stale(&[entry(None, None)], &runs, time(10, 7)), Some(true));
+
+        // A model the site has never had doesn't make it stale.
+        let gfs_and_nam4km = entry(Some(vec![Model::GFS, Model::NAM4KM]), None);
+        assert_eq!(is_stale(&[gfs_and_nam4km], &runs, now), Some(false));
+
+        // Unless it has no data for any of the models.
+        assert_eq!(is_stale(&[entry(Some(vec![Model::NAM4KM]), None)], &runs, now), Some(true));
+        assert_eq!(is_stale(&[entry(None, None)], &BTreeMap::new(), now), Some(true));
+
+        // Stale on any active list makes the site stale.
+        let entries = [entry(Some(vec![Model::GFS]), None), entry(None, None)];
+        assert_eq!(is_stale(&entries, &runs, now), Some(true));
+    }
+
+    #[test]
+    fn test_cmp_missing_last() {
+        let mut vals = vec![Some(2), None, Some(3), Some(1), None];
+
+        vals.sort_by(|&left, &right| cmp_missing_last(left, right, false));
+        assert_eq!(vals, vec![Some(1), Some(2), Some(3), None, None]);
+
+        vals.sort_by(|&left, &right| cmp_missing_last(left, right, true));
+        assert_eq!(vals, vec![Some(3), Some(2), Some(1), None, None]);
+    }
+
+    #[test]
+    fn test_compass_direction() {
+        let kmso = (46.92, -114.09);
+
+        assert_eq!(compass_direction(kmso, (47.5, -114.09)), "N");
+        assert_eq!(compass_direction(kmso, (46.5, -114.09)), "S");
+        assert_eq!(compass_direction(kmso, (46.92, -113.5)), "E");
+        assert_eq!(compass_direction(kmso, (46.92, -114.7)), "W");
+        assert_eq!(compass_direction(kmso, (47.2, -114.5)), "NW");
+        assert_eq!(compass_direction(kmso, (46.6, -113.6)), "SE");
+    }
+}
@@ -0,0 +1,314 @@
+use bfkmd::{ArchiveIndex, IndexedFile, TablePrinter, bail};
+use bufkit_data::{Archive, Model, StateProv, StationNumber};
+use chrono::Datelike;
+use clap::ArgMatches;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    error::Error,
+    fs,
+    path::Path,
+    str::FromStr,
+    time::SystemTime,
+};
+
+/// How far back to look when calculating the growth rate.
+const GROWTH_DAYS: u64 = 30;
+
+/// The number of files and bytes in a group of files.
+#[derive(Debug, Default, Clone, Copy)]
+struct Tally {
+    files: u64,
+    bytes: u64,
+}
+
+impl Tally {
+    fn add(&mut self, bytes: u64) {
+        self.files += 1;
+        self.bytes += bytes;
+    }
+}
+
+pub fn stats(root: &Path, sub_args: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let arch = Archive::connect(&root)?;
+    let index = ArchiveIndex::open(root)?;
+
+    //
+    // Filters
+    //
+    let sites: HashSet<StationNumber> = sub_args
+        .values_of("sites")
+        .into_iter()
+        .flatten()
+        .map(|site| {
+            site.parse::<u32>()
+                .map(StationNumber::from)
+                .or_else(|_| bfkmd::site_id_to_station_num(&arch, site))
+                .unwrap_or_else(|_| bail(&format!("Unknown site: {}", site)))
+        })
+        .collect();
+
+    let models: Vec<Model> = sub_args
+        .values_of("models")
+        .into_iter()
+        .flatten()
+        .map(|model| {
+            Model::from_str(model)
+                .unwrap_or_else(|_| bail(&format!("Model {} does not exist!", model)))
+        })
+        .collect();
+
+    let state = sub_args.value_of("state").map(|state| {
+        StateProv::from_str(&state.to_uppercase())
+            .unwrap_or_else(|_| bail(&format!("Unable to parse state/providence: {}", state)))
+    });
+
+    let site_info: HashMap<StationNumber, _> = arch
+        .sites()?
+        .into_iter()
+        .map(|site| (site.station_num, site))
+        .collect();
+
+    let files: Vec<IndexedFile> = index
+        .files()?
+        .into_iter()
+        .filter(|file| sites.is_empty() || sites.contains(&file.station_num))
+        .filter(|file| models.is_empty() || models.contains(&file.model))
+        .filter(|file| {
+            state.is_none()
+                || site_info
+                    .get(&file.station_num)
+                    .is_some_and(|site| site.state == state)
+        })
+        .collect();
+
+    //
+    // Tally everything up
+    //
+    let data_dir = root.join("data");
+    let growth_cutoff = SystemTime::now() - std::time::Duration::from_secs(GROWTH_DAYS * 86_400);
+
+    let mut total = Tally::default();
+    let mut recent = Tally::default();
+    let mut by_model: BTreeMap<Model, Tally> = BTreeMap::new();
+    let mut by_year: BTreeMap<i32, Tally> = BTreeMap::new();
+    let mut by_month: BTreeMap<(i32, u32), Tally> = BTreeMap::new();
+    let mut by_site: HashMap<StationNumber, Tally> = HashMap::new();
+    let mut site_ids: HashMap<StationNumber, HashSet<String>> = HashMap::new();
+    let mut num_missing = 0;
+
+    for file in &files {
+        let metadata = match fs::metadata(data_dir.join(&file.file_name)) {
+            Ok(metadata) => metadata,
+            Err(_) => {
+                num_missing += 1;
+                continue;
+            }
+        };
+        let bytes = metadata.len();
+
+        total.add(bytes);
+        by_model.entry(file.model).or_default().add(bytes);
+        by_year.entry(file.init_time.year()).or_default().add(bytes);
+        by_month
+            .entry((file.init_time.year(), file.init_time.month()))
+            .or_default()
+            .add(bytes);
+        by_site.entry(file.station_num).or_default().add(bytes);
+
+        if let Some(ref id) = file.id {
+            site_ids
+                .entry(file.station_num)
+                .or_default()
+                .insert(id.clone());
+        }
+
+        if metadata.modified().is_ok_and(|modified| modified >= growth_cutoff) {
+            recent.add(bytes);
+        }
+    }
+
+    let index_bytes = fs::metadata(root.join("index.db"))?.len();
+
+    //
+    // Summary
+    //
+    let num_days = GROWTH_DAYS as f64;
+    let filtered = !sites.is_empty() || !models.is_empty() || state.is_some();
+    let mut tp = TablePrinter::new()
+        .with_title("Archive Statistics".to_owned())
+        .with_header(format!("{}", root.display()))
+        .with_column::<&str, String>("", &[])
+        .with_column::<&str, String>("Value", &[]);
+    tp.add_row(vec!["Sites".to_owned(), by_site.len().to_string()]);
+    tp.add_row(vec!["Files".to_owned(), total.files.to_string()]);
+    tp.add_row(vec!["Data size".to_owned(), human_size(total.bytes)]);
+    tp.add_row(vec!["Index size".to_owned(), human_size(index_bytes)]);
+    tp.add_row(vec![
+        format!("Files added, last {} days", GROWTH_DAYS),
+        recent.files.to_string(),
+    ]);
+    tp.add_row(vec![
+        format!("Data added, last {} days", GROWTH_DAYS),
+        human_size(recent.bytes),
+    ]);
+    tp.add_row(vec![
+        "Growth per day".to_owned(),
+        human_size((recent.bytes as f64 / num_days) as u64),
+    ]);
+    tp.add_row(vec![
+        "Growth per year (projected)".to_owned(),
+        human_size((recent.bytes as f64 / num_days * 365.0) as u64),
+    ]);
+    if filtered {
+        tp = tp.with_footer("The index size is for the whole archive.".to_owned());
+    }
+    tp.print()?;
+
+    if num_missing > 0 {
+        println!(
+            "{} files in the index are missing from the data directory, try 'bkam fix'.",
+            num_missing
+        );
+    }
+
+    if total.files == 0 {
+        return Ok(());
+    }
+
+    //
+    // By model
+    //
+    let mut tp = TablePrinter::new()
+        .with_title("By Model".to_owned())
+        .with_column::<&str, String>("Model", &[])
+        .with_column::<&str, String>("Files", &[])
+        .with_column::<&str, String>("Size", &[]);
+    for (model, tally) in &by_model {
+        tp.add_row(vec![
+            model.to_string(),
+            tally.files.to_string(),
+            human_size(tally.bytes),
+        ]);
+    }
+    tp.print()?;
+
+    //
+    // By year
+    //
+    let mut tp = TablePrinter::new()
+        .with_title("By Year".to_owned())
+        .with_column::<&str, String>("Year", &[])
+        .with_column::<&str, String>("Files", &[])
+        .with_column::<&str, String>("Size", &[]);
+    for (year, tally) in &by_year {
+        tp.add_row(vec![
+            year.to_string(),
+            tally.files.to_string(),
+            human_size(tally.bytes),
+        ]);
+    }
+    tp.print()?;
+
+    //
+    // By month
+    //
+    let mut tp = TablePrinter::new()
+        .with_title("By Month".to_owned())
+        .with_column::<&str, String>("Month", &[])
+        .with_column::<&str, String>("Files", &[])
+        .with_column::<&str, String>("Size", &[]);
+    for ((year, month), tally) in &by_month {
+        tp.add_row(vec![
+            format!("{}-{:02}", year, month),
+            tally.files.to_string(),
+            human_size(tally.bytes),
+        ]);
+    }
+    tp.print()?;
+
+    //
+    // By site, largest first
+    //
+    let top: usize = sub_args
+        .value_of("top")
+        .map(|top| {
+            top.parse()
+                .unwrap_or_else(|_| bail(&format!("Invalid number of sites: {}", top)))
+        })
+        .unwrap_or(20);
+
+    let mut site_tallies: Vec<(StationNumber, Tally)> = by_site.into_iter().collect();
+    site_tallies.sort_unstable_by_key(|(_, tally)| std::cmp::Reverse(tally.bytes));
+
+    let mut tp = TablePrinter::new()
+        .with_title("By Site".to_owned())
+        .with_column::<&str, String>("Stn Num", &[])
+        .with_column::<&str, String>("IDs", &[])
+        .with_column::<&str, String>("Name", &[])
+        .with_column::<&str, String>("Files", &[])
+        .with_column::<&str, String>("Size", &[]);
+    if site_tallies.len() > top {
+        tp = tp.with_header(format!(
+            "The largest {} of {} sites",
+            top,
+            site_tallies.len()
+        ));
+    }
+    for (station_num, tally) in site_tallies.into_iter().take(top) {
+        let mut ids: Vec<String> = site_ids
+            .remove(&station_num)
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        ids.sort_unstable();
+
+        let name = site_info
+            .get(&station_num)
+            .and_then(|site| site.name.clone())
+            .unwrap_or_else(|| "-".to_owned());
+
+        tp.add_row(vec![
+            station_num.to_string(),
+            ids.join(", "),
+            name,
+            tally.files.to_string(),
+            human_size(tally.bytes),
+        ]);
+    }
+    tp.print()?;
+
+    Ok(())
+}
+
+/// Format a number of bytes for people to read.
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod unit {
+    use super::*;
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(0), "0 B");
+        assert_eq!(human_size(1023), "1023 B");
+        assert_eq!(human_size(1024), "1.0 KiB");
+        assert_eq!(human_size(274_000), "267.6 KiB");
+        assert_eq!(human_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+        assert_eq!(human_size(u64::MAX), "16777216.0 TiB");
+    }
+}
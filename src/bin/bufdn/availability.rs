@@ -0,0 +1,204 @@
+use bufkit_data::{BufkitDataErr, Model};
+use chrono::{NaiveDateTime, Timelike};
+use rusqlite::{Connection, OpenFlags};
+use std::{collections::HashMap, path::Path, str::FromStr};
+
+/// Keeps track of when model runs show up on the remote servers.
+///
+/// For each site and model run this records the first time a download attempt found it missing
+/// and the first time it was successfully fetched. Runs that were seen missing before they were
+/// fetched bracket the time the run actually became available. Keeping the sites separate means a
+/// site that doesn't exist for a model, or has a stale id, is never counted as a late run.
+pub struct AvailabilityDb {
+    db_conn: Connection,
+}
+
+/// Summary of how long after the initialization time runs for a model and cycle became available.
+pub struct AvailabilityStats {
+    pub model: Model,
+    pub cycle: u32,
+    pub num_runs: usize,
+    pub min_delay_minutes: i64,
+    pub median_delay_minutes: i64,
+    pub pct90_delay_minutes: i64,
+    pub max_delay_minutes: i64,
+}
+
+impl AvailabilityDb {
+    pub fn open_or_create(root: &Path) -> Result<Self, BufkitDataErr> {
+        let db_file = &root.join("availability.db");
+
+        let db_conn = Connection::open_with_flags(
+            db_file,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        )?;
+
+        Self::migrate(&db_conn)?;
+
+        db_conn.execute(
+            "CREATE TABLE IF NOT EXISTS runs (
+                site_id       TEXT NOT NULL,
+                model         TEXT NOT NULL,
+                init_time     TEXT NOT NULL,
+                first_missing TEXT DEFAULT NULL,
+                fetched       TEXT DEFAULT NULL,
+                PRIMARY KEY (site_id, model, init_time)
+            )",
+            [],
+        )?;
+
+        Ok(AvailabilityDb { db_conn })
+    }
+
+    /// Bring an older database up to the current schema, the version is kept in `user_version`.
+    fn migrate(db_conn: &Connection) -> Result<(), BufkitDataErr> {
+        let version: i32 = db_conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        if version < 1 {
+            // Runs used to be kept without the site, those rows can't be split up so start over.
+            db_conn.execute("DROP TABLE IF EXISTS runs", [])?;
+            db_conn.pragma_update(None, "user_version", 1)?;
+        }
+
+        Ok(())
+    }
+
+    /// Record that a run was not available yet. Only the first time is kept, and nothing is
+    /// recorded once the run has been fetched.
+    pub fn record_missing(
+        &self,
+        site_id: &str,
+        model: Model,
+        init_time: NaiveDateTime,
+        now: NaiveDateTime,
+    ) -> Result<(), BufkitDataErr> {
+        self.db_conn.execute(
+            "INSERT INTO runs (site_id, model, init_time, first_missing) VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT (site_id, model, init_time)
+                DO UPDATE SET first_missing = excluded.first_missing
+                WHERE first_missing IS NULL AND fetched IS NULL",
+            rusqlite::params![site_id, model.as_static_str(), init_time, now],
+        )?;
+
+        Ok(())
+    }
+
+    /// Record that a run was successfully downloaded. Only the first time is kept.
+    pub fn record_fetched(
+        &self,
+        site_id: &str,
+        model: Model,
+        init_time: NaiveDateTime,
+        now: NaiveDateTime,
+    ) -> Result<(), BufkitDataErr> {
+        self.db_conn.execute(
+            "INSERT INTO runs (site_id, model, init_time, fetched) VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT (site_id, model, init_time) DO UPDATE SET fetched = excluded.fetched
+                WHERE fetched IS NULL",
+            rusqlite::params![site_id, model.as_static_str(), init_time, now],
+        )?;
+
+        Ok(())
+    }
+
+    /// Calculate the delay statistics for each model and cycle hour.
+    ///
+    /// Only sites that were seen missing before they were fetched are used, otherwise the fetch
+    /// time says more about when bufdn was run than when the data was posted. A run counts as
+    /// available when the first of those sites was fetched.
+    pub fn stats(&self) -> Result<Vec<AvailabilityStats>, BufkitDataErr> {
+        let mut stmt = self.db_conn.prepare(
+            "
+                SELECT model, init_time, MIN(fetched)
+                FROM runs
+                WHERE first_missing IS NOT NULL AND fetched IS NOT NULL
+                GROUP BY model, init_time
+            ",
+        )?;
+
+        let parse_row = |row: &rusqlite::Row| -> Result<(String, NaiveDateTime, NaiveDateTime), _> {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        };
+
+        let mut delays: HashMap<(Model, u32), Vec<i64>> = HashMap::new();
+        for row in stmt.query_map([], parse_row)? {
+            let (model, init_time, fetched) = row?;
+            let model = Model::from_str(&model)?;
+
+            delays
+                .entry((model, init_time.hour()))
+                .or_default()
+                .push((fetched - init_time).num_minutes());
+        }
+
+        let mut stats: Vec<AvailabilityStats> = delays
+            .into_iter()
+            .map(|((model, cycle), mut delays)| {
+                delays.sort_unstable();
+
+                let percentile = |pct: usize| delays[(delays.len() - 1) * pct / 100];
+
+                AvailabilityStats {
+                    model,
+                    cycle,
+                    num_runs: delays.len(),
+                    min_delay_minutes: percentile(0),
+                    median_delay_minutes: percentile(50),
+                    pct90_delay_minutes: percentile(90),
+                    max_delay_minutes: percentile(100),
+                }
+            })
+            .collect();
+
+        stats.sort_unstable_by_key(|st| (st.model, st.cycle));
+
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod unit {
+    use super::*;
+
+    use chrono::NaiveDate;
+    use tempfile::TempDir;
+
+    fn time(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 5, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_stats() {
+        let tmp = TempDir::with_prefix("bfkmd-test-availability").unwrap();
+        let db = AvailabilityDb::open_or_create(tmp.path()).unwrap();
+
+        // Seen missing, then fetched 3:30 and 4:00 after the 12Z runs.
+        db.record_missing("kmso", Model::GFS, time(1, 12, 0), time(1, 14, 0)).unwrap();
+        db.record_fetched("kmso", Model::GFS, time(1, 12, 0), time(1, 15, 30)).unwrap();
+        db.record_missing("kmso", Model::GFS, time(2, 12, 0), time(2, 14, 0)).unwrap();
+        db.record_missing("kmso", Model::GFS, time(2, 12, 0), time(2, 15, 0)).unwrap();
+        db.record_fetched("kmso", Model::GFS, time(2, 12, 0), time(2, 16, 0)).unwrap();
+        db.record_fetched("kmso", Model::GFS, time(2, 12, 0), time(2, 18, 0)).unwrap();
+
+        // Another site with the same run, the earliest fetch counts for the run.
+        db.record_missing("kmsx", Model::GFS, time(2, 12, 0), time(2, 14, 0)).unwrap();
+        db.record_fetched("kmsx", Model::GFS, time(2, 12, 0), time(2, 17, 0)).unwrap();
+
+        // Never seen missing, so it doesn't count.
+        db.record_fetched("kmso", Model::NAM, time(1, 0, 0), time(1, 9, 0)).unwrap();
+
+        // A site that doesn't exist for the model is always missing, it must not hold up the run.
+        db.record_missing("kxxx", Model::NAM, time(1, 0, 0), time(1, 4, 0)).unwrap();
+
+        let stats = db.stats().unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].model, Model::GFS);
+        assert_eq!(stats[0].cycle, 12);
+        assert_eq!(stats[0].num_runs, 2);
+        assert_eq!(stats[0].min_delay_minutes, 210);
+        assert_eq!(stats[0].max_delay_minutes, 240);
+    }
+}
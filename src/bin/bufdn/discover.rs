@@ -0,0 +1,178 @@
+use super::sources::IowaState;
+use bfkmd::{TablePrinter, parse_date_string};
+use bufkit_data::{Archive, BufkitDataErr, Model};
+use chrono::{Duration, NaiveDateTime, Utc};
+use clap::ArgMatches;
+use reqwest::{StatusCode, Url, blocking::Client};
+use std::{error::Error, fs, path::Path, str::FromStr};
+use strum::IntoEnumIterator;
+
+/// How long to wait after a model initialization time before assuming all the files are posted.
+const DISCOVERY_LAG_HOURS: i64 = 6;
+
+/// A site and model combination available upstream but not in the archive.
+#[derive(Debug, Clone)]
+pub struct DiscoveredSite {
+    pub site_id: String,
+    pub model: Model,
+    pub init_time: NaiveDateTime,
+}
+
+/// Crawl the Iowa State directory listings for a recent model run and find the site and model
+/// combinations that are not in the archive catalog yet.
+pub fn discover_new_sites(
+    root: &Path,
+    arg_matches: &ArgMatches,
+) -> Result<Vec<DiscoveredSite>, Box<dyn Error>> {
+    let arch = Archive::connect(&root)?;
+
+    let models: Vec<Model> = if arg_matches.is_present("models") {
+        arg_matches
+            .values_of("models")
+            .into_iter()
+            .flat_map(|model_iter| model_iter.map(Model::from_str))
+            .filter_map(Result::ok)
+            .collect()
+    } else {
+        Model::iter().collect()
+    };
+
+    let host_url = arg_matches
+        .value_of("listing-url")
+        .unwrap_or(IowaState::HOST_URL);
+
+    let target_time = arg_matches
+        .value_of("start")
+        .map(parse_date_string)
+        .unwrap_or_else(|| Utc::now().naive_utc() - Duration::hours(DISCOVERY_LAG_HOURS));
+
+    let mut discovered = vec![];
+    for model in models {
+        // The most recent run at or before the target time.
+        let init_time = match model
+            .all_runs(
+                &(target_time - Duration::hours(model.hours_between_runs())),
+                &target_time,
+            )
+            .last()
+        {
+            Some(init_time) => init_time,
+            None => continue,
+        };
+
+        let index_url = IowaState::index_url(host_url, model, &init_time);
+        println!("Checking {}", index_url);
+
+        let listing = match fetch_listing(&index_url) {
+            Ok(listing) => listing,
+            Err(err) => {
+                println!("  Unable to load directory listing: {}", err);
+                continue;
+            }
+        };
+
+        for site_id in IowaState::parse_index(&listing, model) {
+            match arch.station_num_for_id(&site_id, model) {
+                Ok(_) => {}
+                Err(BufkitDataErr::NotInIndex) => discovered.push(DiscoveredSite {
+                    site_id,
+                    model,
+                    init_time,
+                }),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    Ok(discovered)
+}
+
+/// Print a table of the newly discovered sites.
+pub fn print_discovered(discovered: &[DiscoveredSite]) -> Result<(), Box<dyn Error>> {
+    if discovered.is_empty() {
+        println!("No new sites found upstream.");
+        return Ok(());
+    }
+
+    let mut tp = TablePrinter::new()
+        .with_title("Sites available upstream but not in the archive".to_owned())
+        .with_column::<&str, String>("Model", &[])
+        .with_column::<&str, String>("ID", &[])
+        .with_column::<&str, String>("Run", &[]);
+
+    for site in discovered {
+        tp.add_row(vec![
+            site.model.to_string(),
+            site.site_id.clone(),
+            site.init_time.format("%Y-%m-%d %HZ").to_string(),
+        ]);
+    }
+
+    tp.print()?;
+
+    Ok(())
+}
+
+/// Load a directory listing from a web server, or from a local directory or file standing in for
+/// one.
+fn fetch_listing(url: &str) -> Result<String, Box<dyn Error>> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        let response = Client::new().get(url).send()?;
+
+        match response.status() {
+            StatusCode::OK => Ok(response.text()?),
+            code => Err(format!("HTTP error ({}): {}", code, url).into()),
+        }
+    } else {
+        let path = if url.starts_with("file://") {
+            Url::parse(url)?
+                .to_file_path()
+                .map_err(|_| format!("Unable to decode file url: {}", url))?
+        } else {
+            Path::new(url).to_path_buf()
+        };
+
+        // Like a web server, prefer an index page if the directory has one.
+        if path.is_dir() && path.join("index.html").is_file() {
+            Ok(fs::read_to_string(path.join("index.html"))?)
+        } else if path.is_dir() {
+            let names: Vec<String> = fs::read_dir(&path)?
+                .filter_map(|e| e.ok())
+                .filter_map(|e| e.file_name().to_str().map(ToOwned::to_owned))
+                .collect();
+
+            Ok(names.join("\n"))
+        } else {
+            Ok(fs::read_to_string(&path)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit {
+    use super::*;
+
+    use std::fs::File;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_fetch_listing_local_directory() {
+        let tmp = TempDir::with_prefix("bfkmd-test-discover").unwrap();
+        for name in ["gfs3_kmso.buf", "nam_kmso.buf", "notes.txt"] {
+            File::create(tmp.path().join(name)).unwrap();
+        }
+
+        let dir = tmp.path().to_str().unwrap();
+        let listing = fetch_listing(dir).unwrap();
+        assert_eq!(IowaState::parse_index(&listing, Model::GFS), vec!["KMSO"]);
+        assert_eq!(IowaState::parse_index(&listing, Model::NAM), vec!["KMSO"]);
+
+        let url = Url::from_directory_path(tmp.path()).unwrap();
+        assert_eq!(fetch_listing(url.as_str()).unwrap().len(), listing.len());
+
+        // An index page takes the place of the directory contents.
+        fs::write(tmp.path().join("index.html"), "<a href=\"gfs3_kgpi.buf\">").unwrap();
+        let listing = fetch_listing(dir).unwrap();
+        assert_eq!(IowaState::parse_index(&listing, Model::GFS), vec!["KGPI"]);
+    }
+}
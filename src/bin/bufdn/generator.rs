@@ -1,12 +1,13 @@
+use super::discover::DiscoveredSite;
 use super::sources::{IowaState, Source};
 use super::{DEFAULT_DAYS_BACK, ReqInfo, StepResult};
-use crate::missing_url::MissingUrlDb;
-use bfkmd::{AutoDownloadListDb, parse_date_string};
+use bfkmd::{AutoDownloadListDb, MissingUrlDb, SiteRegistryDb, parse_date_string};
 use bufkit_data::{Archive, BufkitDataErr, Model, StationNumber};
 use chrono::{Duration, NaiveDateTime, Utc};
 use clap::ArgMatches;
 use crossbeam_channel as channel;
 use std::{
+    collections::HashMap,
     error::Error,
     fs,
     path::{Path, PathBuf},
@@ -62,7 +63,7 @@ pub fn start_generator_thread(
                 .map(|(e, model, e_str)| {
                     let site_id = e_str
                         .split('.')
-                        .nth(0)
+                        .next()
                         .and_then(|s| s.split('_').nth(1))
                         .map(String::from);
 
@@ -70,7 +71,7 @@ pub fn start_generator_thread(
                 })
                 // Build URL from the path
                 .map(|(e, model, site_id)| {
-                    let url = reqwest::Url::from_file_path(&e);
+                    let url = reqwest::Url::from_file_path(e);
                     (e, model, url, site_id)
                 })
                 .map(
@@ -100,11 +101,8 @@ pub fn start_generator_thread(
                         }
                     },
                 )
-                .for_each(move |request| {
-                    if generator_tx.send(request).is_err() {
-                        return;
-                    }
-                });
+                .try_for_each(move |request| generator_tx.send(request))
+                .ok();
         });
     } else {
         let models: Vec<Model> = if arg_matches.is_present("models") {
@@ -137,7 +135,12 @@ pub fn start_generator_thread(
         let sites: Vec<String> = arg_matches
             .values_of("sites")
             .map(|site_iter| site_iter.map(|s| s.to_string()).collect())
-            .unwrap_or_else(Vec::new);
+            .unwrap_or_default();
+
+        let list = arg_matches
+            .value_of("list")
+            .unwrap_or(AutoDownloadListDb::DEFAULT_LIST)
+            .to_owned();
 
         spawn(move || {
             let missing_urls = match MissingUrlDb::open_or_create_404_db(&root) {
@@ -150,7 +153,7 @@ pub fn start_generator_thread(
                 }
             };
 
-            let download_list = match build_download_list(&arch, sites, &models, start, end) {
+            let download_list = match build_download_list(&arch, sites, &list, &models, start, end) {
                 Ok(a_vec) => a_vec,
                 Err(err) => {
                     generator_tx
@@ -181,46 +184,68 @@ pub fn start_generator_thread(
                 .take(1_500)
                 // Pass it off to another thread for downloading.
                 .map(StepResult::Request)
-                .for_each(move |request| {
-                    if generator_tx.send(request).is_err() {
-                        return;
-                    }
-                });
+                .try_for_each(move |request| generator_tx.send(request))
+                .ok();
         });
     }
 
     Ok(())
 }
 
+/// Request the newly discovered sites so they get added to the archive catalog.
+pub fn start_discovered_generator_thread(
+    discovered: Vec<DiscoveredSite>,
+    generator_tx: channel::Sender<StepResult>,
+) {
+    spawn(move || {
+        let source = IowaState {};
+
+        discovered
+            .into_iter()
+            .filter_map(|site| {
+                source.build_req_info(site.site_id, None, site.model, site.init_time)
+            })
+            .map(StepResult::Request)
+            .try_for_each(move |request| generator_tx.send(request))
+            .ok();
+    });
+}
+
+type DownloadItem = (String, Option<StationNumber>, Model, NaiveDateTime);
+
 fn build_download_list(
     arch: &Archive,
     sites: Vec<String>,
+    list: &str,
     models: &[Model],
     start: NaiveDateTime,
     end: NaiveDateTime,
-) -> Result<Vec<(String, Option<StationNumber>, Model, NaiveDateTime)>, BufkitDataErr> {
+) -> Result<Vec<DownloadItem>, BufkitDataErr> {
     use std::time::Instant;
 
     let start_long_request = Instant::now();
     let site_model: Vec<(String, Option<StationNumber>, Model)> = if !sites.is_empty() {
         println!("Using provided sites...");
+        let registry = SiteRegistryDb::open_or_create(arch.root())?;
         models
             .iter()
-            .flat_map(|&model| {
-                sites.iter().map(|s| s.to_lowercase()).map(move |s| {
-                    let stn_num = arch.station_num_for_id(&s, model).ok();
-                    (s, stn_num, model)
-                })
+            .flat_map(|&model| sites.iter().map(move |s| (s.to_lowercase(), model)))
+            .map(|(s, model)| {
+                let stn_num = match arch.station_num_for_id(&s, model) {
+                    Ok(stn_num) => Some(stn_num),
+                    Err(_) => registry.station_num_for_id(&s, model)?,
+                };
+                Ok((s, stn_num, model))
             })
-            .collect()
+            .collect::<Result<_, BufkitDataErr>>()?
     } else {
-        println!("Make download list of sites...");
-        list_of_auto_download(arch)?
+        println!("Make download list of sites from the {} list...", list);
+        list_of_auto_download(arch, list)?
     };
     let duration = start_long_request.elapsed();
     println!("....done! with sites it took: {:?}", duration);
 
-    let mut to_ret: Vec<(String, Option<StationNumber>, Model, NaiveDateTime)> = site_model
+    let mut to_ret: Vec<DownloadItem> = site_model
         .iter()
         .flat_map(|(id, stn, model)| {
             model
@@ -239,21 +264,70 @@ fn build_download_list(
 
 fn list_of_auto_download(
     arch: &Archive,
+    list: &str,
 ) -> Result<Vec<(String, Option<StationNumber>, Model)>, BufkitDataErr> {
-    let dl_db = AutoDownloadListDb::open_or_create(&arch.root())?;
-    let mut dl_stations = dl_db.get_list()?;
-    dl_stations.sort_unstable();
-
-    let mut dl_id_stations: Vec<(String, Option<StationNumber>, Model)> = Vec::new();
+    let dl_db = AutoDownloadListDb::open_or_create(arch.root())?.with_list(list);
+    let entries = dl_db.get_entries()?;
 
+    let mut ids: HashMap<(StationNumber, Model), String> = HashMap::new();
     for model in Model::iter() {
         arch.sites_and_ids_for(model)?
             .into_iter()
-            .filter(|(site, _id)| dl_stations.binary_search(&site.station_num).is_ok())
             .for_each(|(site, id)| {
-                dl_id_stations.push((id, Some(site.station_num), model));
+                ids.insert((site.station_num, model), id);
             })
     }
 
+    // Ids registered with bkam, for sites that haven't been downloaded yet or to replace the ids
+    // in the files. Retired ids are never used.
+    let registry = SiteRegistryDb::open_or_create(arch.root())?;
+    for entry in &entries {
+        let stn = entry.station_num;
+
+        for (model, id) in registry.retired_ids_for(stn)? {
+            if ids
+                .get(&(stn, model))
+                .is_some_and(|current| current.eq_ignore_ascii_case(&id))
+            {
+                ids.remove(&(stn, model));
+            }
+        }
+
+        // When a model has more than one registered id, the one added last is used.
+        for (model, id) in registry.ids_for(stn)? {
+            ids.insert((stn, model), id);
+        }
+    }
+
+    let mut dl_id_stations: Vec<(String, Option<StationNumber>, Model)> = Vec::new();
+
+    // Sites outside their download window are skipped.
+    let today = Utc::now().date_naive();
+    for entry in entries.into_iter().filter(|entry| entry.is_active(today)) {
+        let stn = entry.station_num;
+
+        match entry.models {
+            None => {
+                for model in Model::iter() {
+                    if let Some(id) = ids.get(&(stn, model)) {
+                        dl_id_stations.push((id.clone(), Some(stn), model));
+                    }
+                }
+            }
+            Some(models) => {
+                for model in models {
+                    // If this model was never downloaded for the site, try an id from another one.
+                    let id = ids
+                        .get(&(stn, model))
+                        .or_else(|| Model::iter().find_map(|m| ids.get(&(stn, m))));
+
+                    if let Some(id) = id {
+                        dl_id_stations.push((id.clone(), Some(stn), model));
+                    }
+                }
+            }
+        }
+    }
+
     Ok(dl_id_stations)
 }
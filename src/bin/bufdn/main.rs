@@ -1,19 +1,24 @@
 //! Bufkit Downloader.
 //!
 //! Downloads Bufkit files and stores them in your archive.
-use crate::missing_url::MissingUrlDb;
+use crate::availability::AvailabilityDb;
+use bfkmd::{MissingUrlDb, TablePrinter};
 use bufkit_data::{Model, StationNumber};
 use chrono::{Duration, NaiveDateTime, Utc};
 use clap::{crate_version, Arg, ArgMatches, Command};
 use crossbeam_channel as channel;
 use dirs::home_dir;
 use reqwest::StatusCode;
-use std::{error::Error, path::PathBuf};
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
 
+mod availability;
 mod db_writer;
+mod discover;
 mod download;
 mod generator;
-mod missing_url;
 mod sources;
 
 const DEFAULT_DAYS_BACK: i64 = 2;
@@ -44,21 +49,57 @@ fn run() -> Result<(), Box<dyn Error>> {
         .or_else(|| home_dir().map(|hd| hd.join("bufkit")))
         .expect("Invalid root.");
 
+    if matches.is_present("latency-report") {
+        return print_latency_report(&root);
+    }
+
+    let discovered = if matches.is_present("discover") {
+        let discovered = discover::discover_new_sites(&root, &matches)?;
+        discover::print_discovered(&discovered)?;
+
+        if !matches.is_present("register") {
+            return Ok(());
+        }
+
+        Some(discovered)
+    } else {
+        None
+    };
+
     let (generator_tx, dl_rx) = channel::bounded::<StepResult>(CAPACITY);
     let (dl_tx, save_rx) = channel::bounded::<StepResult>(CAPACITY);
     let (save_tx, print_rx) = channel::bounded::<StepResult>(CAPACITY);
 
-    generator::start_generator_thread(root.clone(), &matches, generator_tx)?;
+    match discovered {
+        Some(discovered) => generator::start_discovered_generator_thread(discovered, generator_tx),
+        None => generator::start_generator_thread(root.clone(), &matches, generator_tx)?,
+    }
     download::start_download_threads(dl_rx, dl_tx);
     db_writer::start_writer_thread(root.clone(), save_rx, save_tx);
 
     let too_old_to_be_missing = Utc::now().naive_utc() - Duration::hours(27);
     let missing_urls = MissingUrlDb::open_or_create_404_db(&root)?;
+    let availability = AvailabilityDb::open_or_create(&root)?;
 
     for step_result in print_rx {
         use crate::StepResult::*;
 
         match step_result {
+            URLNotFound(ReqInfo {
+                ref site_id,
+                model,
+                init_time: Some(init_time),
+                ..
+            }) if init_time >= too_old_to_be_missing => {
+                availability.record_missing(site_id, model, init_time, Utc::now().naive_utc())?;
+
+                let msg = handle_error_as_missing_data(
+                    &step_result,
+                    too_old_to_be_missing,
+                    &missing_urls,
+                )?;
+                println!("{}", msg);
+            }
             URLNotFound(_) | ParseError(_, _) | OtherDownloadError(_, _) | ArchiveError(_, _) => {
                 let msg = handle_error_as_missing_data(
                     &step_result,
@@ -71,6 +112,11 @@ fn run() -> Result<(), Box<dyn Error>> {
                 println!("  HTTP error ({}): {}.", code, url)
             }
             Success(req) => {
+                if let Some(init_time) = req.init_time {
+                    let now = Utc::now().naive_utc();
+                    availability.record_fetched(&req.site_id, req.model, init_time, now)?;
+                }
+
                 let req_init_time_str = req
                     .init_time
                     .map(|r| format!("{}", r.format("%Y-%m-%d %H")))
@@ -142,6 +188,52 @@ fn handle_error_as_missing_data(
     Ok(msg)
 }
 
+fn print_latency_report(root: &Path) -> Result<(), Box<dyn Error>> {
+    let availability = AvailabilityDb::open_or_create(root)?;
+    let stats = availability.stats()?;
+
+    if stats.is_empty() {
+        println!("No model runs have been seen becoming available yet.");
+        return Ok(());
+    }
+
+    let as_hours = |minutes: i64| format!("{}:{:02}", minutes / 60, minutes % 60);
+
+    let mut tp = TablePrinter::new()
+        .with_title("Model Run Availability".to_owned())
+        .with_header(
+            "Hours:minutes after the initialization time until a run was first fetched.".to_owned(),
+        )
+        .with_footer(concat!(
+            "Only runs that were tried and found missing before they were downloaded are used, ",
+            "so these times are limited by how often bufdn runs."
+        )
+        .to_owned())
+        .with_column::<&str, String>("Model", &[])
+        .with_column::<&str, String>("Cycle", &[])
+        .with_column::<&str, String>("Runs", &[])
+        .with_column::<&str, String>("Min", &[])
+        .with_column::<&str, String>("Median", &[])
+        .with_column::<&str, String>("90%", &[])
+        .with_column::<&str, String>("Max", &[]);
+
+    for st in stats {
+        tp.add_row(vec![
+            st.model.to_string(),
+            format!("{:02}Z", st.cycle),
+            st.num_runs.to_string(),
+            as_hours(st.min_delay_minutes),
+            as_hours(st.median_delay_minutes),
+            as_hours(st.pct90_delay_minutes),
+            as_hours(st.max_delay_minutes),
+        ]);
+    }
+
+    tp.print()?;
+
+    Ok(())
+}
+
 fn parse_args() -> ArgMatches {
     Command::new("bufdn")
         .author("Ryan <rnleach@users.noreply.github.com>")
@@ -181,9 +273,7 @@ fn parse_args() -> ArgMatches {
                 .takes_value(true)
                 .conflicts_with_all(&["start", "end"])
                 .help("Number of days back to consider.")
-                .long_help(concat!(
-                    "The number of days back to consider. Cannot use --start or --end with this."
-                )),
+                .long_help("The number of days back to consider. Cannot use --start or --end with this."),
         )
         .arg(
             Arg::new("start")
@@ -216,9 +306,19 @@ fn parse_args() -> ArgMatches {
                 .long_help(
                     "Set the root directory of the archive you are invoking this command for.",
                 )
-                .conflicts_with("create")
                 .global(true),
         )
+        .arg(
+            Arg::new("list")
+                .long("list")
+                .takes_value(true)
+                .conflicts_with("sites")
+                .help("The named auto download list to use, default is 'default'.")
+                .long_help(concat!(
+                    "The named auto download list to use when no sites are given. Lists are ",
+                    "managed with 'bkam sites modify --list'."
+                )),
+        )
         .arg(
             Arg::new("local")
                 .short('l')
@@ -227,8 +327,59 @@ fn parse_args() -> ArgMatches {
                 .default_missing_value(".")
                 .help("Import files from a local directory."),
         )
+        .arg(
+            Arg::new("latency-report")
+                .long("latency-report")
+                .conflicts_with_all(&["sites", "models", "days-back", "start", "end", "local", "list"])
+                .help("Report how long after initialization model runs become available.")
+                .long_help(concat!(
+                    "Report how long after the initialization time model runs become available ",
+                    "for download, by model and cycle. Every run of bufdn records when a run was ",
+                    "first found missing and when it was first downloaded for each site. Sites ",
+                    "that are never downloaded, like ones a model doesn't have, are not counted. ",
+                    "No data is downloaded with this option."
+                )),
+        )
+        .arg(
+            Arg::new("discover")
+                .long("discover")
+                .conflicts_with_all(&["sites", "days-back", "end", "local", "latency-report"])
+                .help("Find sites available upstream that are not in the archive.")
+                .long_help(concat!(
+                    "Crawl the Iowa State directory listings for a recent model run and list the ",
+                    "site and model combinations that are available there but are not in the ",
+                    "archive. Use --models to limit the search and --start to pick the model run, ",
+                    "otherwise the most recent run at least 6 hours old is used. Nothing is ",
+                    "downloaded unless --register is also given."
+                )),
+        )
+        .arg(
+            Arg::new("register")
+                .long("register")
+                .requires("discover")
+                .help("Add the sites found with --discover to the archive.")
+                .long_help(concat!(
+                    "Download the model run used with --discover for each new site, which adds ",
+                    "the site to the archive. After that the site can be configured with bkam."
+                )),
+        )
+        .arg(
+            Arg::new("listing-url")
+                .long("listing-url")
+                .takes_value(true)
+                .requires("discover")
+                .help("Base URL, or local directory, to crawl with --discover.")
+                .long_help(concat!(
+                    "Base URL to crawl for directory listings with --discover instead of the Iowa ",
+                    "State archive. This may also be a local directory laid out like the archive, ",
+                    "e.g. DIR/YYYY/MM/DD/bufkit/HH/gfs/, where a directory of files or a saved ",
+                    "index page stands in for the listing. Downloads with --register always use ",
+                    "the Iowa State archive."
+                )),
+        )
         .after_help(concat!(
-            "To download data for a new site for the first time you must also specify the model."
+            "To download data for a new site for the first time you must also specify the model, ",
+            "or register the site with 'bkam sites add' first."
         ))
         .get_matches()
 }
@@ -1,7 +1,7 @@
 use super::ReqInfo;
 use bufkit_data::Model;
 use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
-use std::ops::Deref;
+use std::{ops::Deref, str::FromStr};
 
 pub trait Source {
     fn build_req_info(
@@ -44,50 +44,70 @@ impl Source for IowaState {
 }
 
 impl IowaState {
-    const HOST_URL: &'static str = "http://mtarchive.geol.iastate.edu/";
+    pub const HOST_URL: &'static str = "http://mtarchive.geol.iastate.edu/";
 
     fn build_url(site: &str, model: Model, init_time: &NaiveDateTime) -> String {
         let site = site.to_lowercase();
 
-        let year = init_time.year();
-        let month = init_time.month();
-        let day = init_time.day();
-        let hour = init_time.hour();
-        let remote_model = match (model, hour) {
-            (Model::GFS, _) => "gfs3",
-            (Model::NAM, 6) | (Model::NAM, 18) => "namm",
-            (Model::NAM, _) => "nam",
-            (Model::NAM4KM, _) => "nam4km",
-        };
+        let remote_file_name =
+            Self::remote_model(model, init_time.hour()).to_string() + "_" + &site + ".buf";
 
-        let remote_file_name = remote_model.to_string() + "_" + &site + ".buf";
+        Self::index_url(Self::HOST_URL, model, init_time) + &remote_file_name
+    }
 
+    /// The URL of the directory holding all the files for a model run.
+    pub fn index_url(host_url: &str, model: Model, init_time: &NaiveDateTime) -> String {
         format!(
-            "{}{}/{:02}/{:02}/bufkit/{:02}/{}/{}",
-            Self::HOST_URL,
-            year,
-            month,
-            day,
-            hour,
+            "{}{}/{:02}/{:02}/bufkit/{:02}/{}/",
+            host_url,
+            init_time.year(),
+            init_time.month(),
+            init_time.day(),
+            init_time.hour(),
             model.to_string().to_lowercase(),
-            remote_file_name
         )
     }
 
+    /// Parse the site ids out of the directory listing for a model run.
+    ///
+    /// This works on anything with the file names in it, an html index page or a plain list of
+    /// file names.
+    pub fn parse_index(listing: &str, model: Model) -> Vec<String> {
+        let mut site_ids: Vec<String> = listing
+            .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+            .filter_map(|token| token.strip_suffix(".buf"))
+            .filter_map(|token| token.split_once('_'))
+            .filter(|(remote_model, _)| Model::from_str(remote_model).ok() == Some(model))
+            .map(|(_, site)| site.to_uppercase())
+            .filter(|site| !site.is_empty())
+            .collect();
+
+        site_ids.sort_unstable();
+        site_ids.dedup();
+
+        site_ids
+    }
+
+    fn remote_model(model: Model, hour: u32) -> &'static str {
+        match (model, hour) {
+            (Model::GFS, _) => "gfs3",
+            (Model::NAM, 6) | (Model::NAM, 18) => "namm",
+            (Model::NAM, _) => "nam",
+            (Model::NAM4KM, _) => "nam4km",
+        }
+    }
+
     fn fix_known_issues_with_site_mismatch_in_url_and_in_the_file(
         site_id: String,
         model: Model,
         init_time: NaiveDateTime,
     ) -> String {
-        if site_id == "KLDN"
-            && model != Model::GFS
-            && init_time >= NaiveDate::from_ymd_opt(2020, 5, 1).unwrap().and_hms_opt(0, 0, 0).unwrap()
-        {
-            "KDLN".to_owned()
-        } else if site_id == "KLDN"
-            && model == Model::GFS
-            && init_time >= NaiveDate::from_ymd_opt(2021, 3, 22).unwrap().and_hms_opt(18, 0, 0).unwrap()
-        {
+        let non_gfs_moved = model != Model::GFS
+            && init_time >= NaiveDate::from_ymd_opt(2020, 5, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let gfs_moved = model == Model::GFS
+            && init_time >= NaiveDate::from_ymd_opt(2021, 3, 22).unwrap().and_hms_opt(18, 0, 0).unwrap();
+
+        if site_id == "KLDN" && (non_gfs_moved || gfs_moved) {
             "KDLN".to_owned()
         } else {
             site_id
@@ -163,3 +183,47 @@ impl IowaState {
         model_site_mismatch || model_init_time_mismatch || expired_sites
     }
 }
+
+#[cfg(test)]
+mod unit {
+    use super::*;
+
+    // Trimmed from the index page for a 12Z run.
+    const LISTING: &str = r#"<!DOCTYPE HTML PUBLIC "-//W3C//DTD HTML 3.2 Final//EN">
+<html>
+ <head>
+  <title>Index of /2024/05/01/bufkit/12/gfs</title>
+ </head>
+ <body>
+<h1>Index of /2024/05/01/bufkit/12/gfs</h1>
+<pre><img src="/icons/blank.gif" alt="Icon "> <a href="?C=N;O=D">Name</a>
+<img src="/icons/back.gif" alt="[PARENTDIR]"> <a href="/2024/05/01/bufkit/12/">Parent Directory</a>
+<img src="/icons/unknown.gif" alt="[   ]"> <a href="gfs3_kmso.buf">gfs3_kmso.buf</a>  2024-05-01 15:41  274K
+<img src="/icons/unknown.gif" alt="[   ]"> <a href="gfs3_kgpi.buf">gfs3_kgpi.buf</a>  2024-05-01 15:41  274K
+<img src="/icons/unknown.gif" alt="[   ]"> <a href="gfs3_kmso.buf">gfs3_kmso.buf</a>  2024-05-01 15:41  274K
+<img src="/icons/unknown.gif" alt="[   ]"> <a href="nam_kbtm.buf">nam_kbtm.buf</a>  2024-05-01 15:10  390K
+<img src="/icons/unknown.gif" alt="[   ]"> <a href="gfs3_.buf">gfs3_.buf</a>  2024-05-01 15:41  0
+<img src="/icons/unknown.gif" alt="[   ]"> <a href="gfs3kbil.buf">gfs3kbil.buf</a>  2024-05-01 15:41  274K
+<img src="/icons/unknown.gif" alt="[   ]"> <a href="gfs3_kbzn.buf.gz">gfs3_kbzn.buf.gz</a>  2024-05-01 15:41  80K
+<img src="/icons/unknown.gif" alt="[   ]"> <a href="bogus_kgtf.buf">bogus_kgtf.buf</a>  2024-05-01 15:41  274K
+<img src="/icons/text.gif" alt="[TXT]"> <a href="README.txt">README.txt</a>  2024-05-01 00:00  1K
+</pre>
+</body></html>
+"#;
+
+    #[test]
+    fn test_parse_index() {
+        assert_eq!(IowaState::parse_index(LISTING, Model::GFS), vec!["KGPI", "KMSO"]);
+        assert_eq!(IowaState::parse_index(LISTING, Model::NAM), vec!["KBTM"]);
+        assert!(IowaState::parse_index(LISTING, Model::NAM4KM).is_empty());
+    }
+
+    #[test]
+    fn test_parse_index_plain_list() {
+        let listing = "namm_kmso.buf\nnamm_kmso.buf\nnam4km_kgpi.buf\n\nnot a file\n";
+
+        assert_eq!(IowaState::parse_index(listing, Model::NAM), vec!["KMSO"]);
+        assert_eq!(IowaState::parse_index(listing, Model::NAM4KM), vec!["KGPI"]);
+        assert!(IowaState::parse_index("", Model::GFS).is_empty());
+    }
+}
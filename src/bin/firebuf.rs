@@ -1,5 +1,5 @@
 //! firebuf - Calculate fire weather indicies from soundings in your Bufkit Archive.
-use bfkmd::{TablePrinter, bail, parse_date_string};
+use bfkmd::{LocalTimeZone, SiteRegistryDb, TablePrinter, bail, parse_date_string};
 use bufkit_data::{Archive, BufkitDataErr, Model, SiteInfo};
 use chrono::{Duration, NaiveDate, NaiveDateTime, Timelike};
 use clap::{Arg, Command, crate_version};
@@ -7,7 +7,13 @@ use dirs::home_dir;
 use metfor::Quantity;
 use sounding_analysis::Sounding;
 use sounding_bufkit::BufkitData;
-use std::{collections::HashMap, error::Error, fs::File, path::PathBuf, str::FromStr};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::File,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 use strum::IntoEnumIterator;
 use strum_macros::{EnumIter, EnumString, IntoStaticStr};
 use textplots::{Chart, Shape, ColorPlot};
@@ -35,6 +41,7 @@ fn run() -> Result<(), Box<dyn Error>> {
     println!("{:#?}", args);
 
     let arch = &Archive::connect(&args.root)?;
+    let registry = SiteRegistryDb::open(&args.root)?;
     let g_stats = &args.graph_stats;
     let t_stats = &args.table_stats;
 
@@ -48,9 +55,10 @@ fn run() -> Result<(), Box<dyn Error>> {
                     Ok(station_num) => Some((site_id.clone(), station_num)),
                     Err(BufkitDataErr::NotInIndex) => {
                         println!(
-                            "No data in archive for {} at {}.",
+                            "No data in archive for {} at {}.{}",
                             model.as_static_str(),
                             site_id,
+                            bfkmd::did_you_mean(arch, site_id),
                         );
                         None
                     }
@@ -71,7 +79,8 @@ fn run() -> Result<(), Box<dyn Error>> {
             };
 
             if args.print {
-                print_stats(&site, &site_id, model, stats, g_stats, t_stats)?;
+                let local_zone = LocalTimeZone::for_site(registry.as_ref(), &site)?;
+                print_stats(&site, &site_id, model, stats, local_zone, g_stats, t_stats)?;
             }
 
             if let Some(ref path) = args.save_dir {
@@ -178,7 +187,7 @@ fn parse_args() -> Result<CmdLineArgs, Box<dyn Error>> {
             Arg::new("print")
                 .long("print")
                 .short('p')
-                .possible_values(&["Y", "N", "y", "n"])
+                .possible_values(["Y", "N", "y", "n"])
                 .default_value("y")
                 .takes_value(true)
                 .help("Print the results to the terminal."),
@@ -257,13 +266,13 @@ fn parse_args() -> Result<CmdLineArgs, Box<dyn Error>> {
         .map(str::to_owned)
         .map(PathBuf::from);
 
-    if let Some(ref save_dir) = save_dir {
-        if !save_dir.is_dir() {
-            bail(&format!(
-                "save-dir path {} does not exist.",
-                save_dir.display()
-            ));
-        }
+    if let Some(ref save_dir) = save_dir
+        && !save_dir.is_dir()
+    {
+        bail(&format!(
+            "save-dir path {} does not exist.",
+            save_dir.display()
+        ));
     }
 
     Ok(CmdLineArgs {
@@ -315,7 +324,7 @@ fn calculate_stats(
         let graph_stats = model_stats
             .graph_stats
             .entry(valid_time)
-            .or_insert_with(HashMap::new);
+            .or_default();
 
         // Build the graph stats
         for &graph_stat in g_stats {
@@ -326,10 +335,7 @@ fn calculate_stats(
                 Pft => sounding_analysis::pft(sounding, 15.0).map(|val| val.unpack()),
                 None => continue,
             };
-            let stat = match stat {
-                Ok(stat) => stat,
-                Err(_) => ::std::f64::NAN,
-            };
+            let stat = stat.unwrap_or(f64::NAN);
 
             graph_stats.insert(graph_stat, stat);
         }
@@ -368,10 +374,7 @@ fn calculate_stats(
                 MinPft => &|snd| sounding_analysis::pft(snd, 15.0).map(|pft| pft.unpack()),
                 TableStatArg::None => continue,
             };
-            let stat = match stat_func(sounding) {
-                Ok(stat) => stat,
-                Err(_) => ::std::f64::NAN,
-            };
+            let stat = stat_func(sounding).unwrap_or(f64::NAN);
 
             let selector: &dyn Fn((f64, u32), (f64, u32)) -> (f64, u32) = match table_stat {
                 Hdw => &zero_z,
@@ -383,9 +386,9 @@ fn calculate_stats(
             let table_stats = model_stats
                 .table_stats
                 .entry(table_stat)
-                .or_insert_with(HashMap::new);
+                .or_default();
 
-            let day_entry = table_stats.entry(cal_day).or_insert((::std::f64::NAN, 12));
+            let day_entry = table_stats.entry(cal_day).or_insert((f64::NAN, 12));
             let hour = valid_time.hour();
 
             *day_entry = selector(*day_entry, (stat, hour));
@@ -402,9 +405,14 @@ fn print_stats(
     site_id: &str,
     model: Model,
     stats: &ModelStats,
+    local_zone: Option<LocalTimeZone>,
     g_stats: &[GraphStatArg],
     t_stats: &[TableStatArg],
 ) -> Result<(), Box<dyn Error>> {
+    let local_time = |time: NaiveDateTime, fmt: &str| {
+        local_zone.map(|zone| zone.format_local(time, fmt))
+    };
+
     //
     // Table
     //
@@ -427,23 +435,29 @@ fn print_stats(
             site_id,
         );
 
+        // Show the local time too if the site has a time zone.
+        let time_str = |time: Option<NaiveDateTime>| match time {
+            Some(time) => match local_time(time, "%Y-%m-%d %H:%M %Z") {
+                Some(local) => format!("{} ({})", time, local),
+                None => time.to_string(),
+            },
+            None => "unknown".to_owned(),
+        };
+
         let header = format!(
             "{} data from {} to {}.",
             model,
-            stats
-                .init_time
-                .map(|dt| dt.to_string())
-                .unwrap_or_else(|| "unknown".to_owned()),
-            stats
-                .end_time
-                .map(|dt| dt.to_string())
-                .unwrap_or_else(|| "unknown".to_owned())
+            time_str(stats.init_time),
+            time_str(stats.end_time),
         );
-        let footer = concat!(
+        let mut footer = concat!(
             "For daily maximum values, first and last days may be partial. ",
             "Days run from 12Z on the date listed until 12Z the next day."
         )
         .to_owned();
+        if let Some(zone) = local_zone {
+            footer.push_str(&format!(" Local times are for {}.", zone));
+        }
 
         let mut tp = TablePrinter::new()
             .with_title(title)
@@ -471,8 +485,19 @@ fn print_stats(
                         }
                     })
                     .collect(),
-                _ => daily_stat_values
-                    .map(|(val, hour)| format!("{:.0} ({:02}Z)", val, hour))
+                _ => days
+                    .iter()
+                    .zip(daily_stat_values)
+                    .map(|(day, (val, hour))| {
+                        // Days start at 12Z, so earlier hours are on the next day.
+                        let valid_time = day.and_hms_opt(hour, 0, 0).unwrap()
+                            + Duration::days(if hour < 12 { 1 } else { 0 });
+
+                        match local_time(valid_time, "%H %Z") {
+                            Some(local) => format!("{:.0} ({:02}Z, {})", val, hour, local),
+                            Option::None => format!("{:.0} ({:02}Z)", val, hour),
+                        }
+                    })
                     .map(|val| {
                         if val.contains("NaN") {
                             "".to_owned()
@@ -508,7 +533,7 @@ fn print_stats(
             });
         }
 
-        let base_time = if let Some(first) = vals.get(0) {
+        let base_time = if let Some(first) = vals.first() {
             first.0
         } else {
             continue;
@@ -519,7 +544,7 @@ fn print_stats(
         let mut min_y = g_stat.default_min_y();
         let values_iter = vals.iter().map(|&(v_time, val)| {
             let x = (v_time - base_time).num_hours() as f32;
-            let y = val as f32;
+            let y = val;
 
             max_x = max_x.max(x);
             max_y = max_y.max(y);
@@ -601,7 +626,7 @@ fn save_stats(
     stats: &ModelStats,
     g_stats: &[GraphStatArg],
     _t_stats: &[TableStatArg],
-    save_dir: &PathBuf,
+    save_dir: &Path,
 ) -> Result<(), Box<dyn Error>> {
     let graph_stats = &stats.graph_stats;
     let mut vts: Vec<NaiveDateTime> = graph_stats.keys().cloned().collect();
@@ -0,0 +1,184 @@
+use crate::haversine_miles;
+use bufkit_data::StateProv;
+use chrono::{NaiveDate, Offset, TimeZone};
+use chrono_tz::Tz;
+use std::{str::FromStr, sync::OnceLock};
+
+/// The embedded list of stations, with columns id, name, state, lat, lon and zone.
+const STATIONS_CSV: &str = include_str!("data/stations.csv");
+
+/// The farthest a station can be from a point and still give it a state, in miles.
+const STATE_MAX_MILES: f64 = 10.0;
+
+/// The farthest a station can be from a point and still give it a time zone, in miles.
+const ZONE_MAX_MILES: f64 = 50.0;
+
+/// A station from the list of well known stations built into the program.
+///
+/// These are mostly airports and upper air sites. The list has no state or time zone boundaries,
+/// so the nearest station only gives a point its state or time zone when the point is close to
+/// it and the other stations nearby agree, see `known_station_state` and `known_station_zone`.
+#[derive(Debug, Clone)]
+pub struct KnownStation {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub state: StateProv,
+    pub lat: f64,
+    pub lon: f64,
+    pub zone: Tz,
+}
+
+/// Get all the known stations.
+pub fn known_stations() -> &'static [KnownStation] {
+    static STATIONS: OnceLock<Vec<KnownStation>> = OnceLock::new();
+
+    STATIONS.get_or_init(|| {
+        STATIONS_CSV
+            .lines()
+            .skip(1)
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| parse_line(line).unwrap_or_else(|| panic!("Invalid station: {}", line)))
+            .collect()
+    })
+}
+
+/// Find the known station nearest to a point, and its distance in miles.
+pub fn nearest_known_station(lat: f64, lon: f64) -> Option<(&'static KnownStation, f64)> {
+    known_stations()
+        .iter()
+        .map(|stn| (stn, haversine_miles((lat, lon), &(stn.lat, stn.lon))))
+        .min_by(|(_, left), (_, right)| left.total_cmp(right))
+}
+
+/// Guess the state of a point from the known stations.
+///
+/// There are no state boundaries in the list, so this is only done when the nearest station is
+/// within `STATE_MAX_MILES` and no station in another state is within twice that distance. A point
+/// next to a station across a state line still gets the wrong state.
+pub fn known_station_state(lat: f64, lon: f64) -> Option<StateProv> {
+    nearest_agreed_station(lat, lon, STATE_MAX_MILES, |left, right| left.state == right.state)
+        .map(|stn| stn.state)
+}
+
+/// Guess the time zone of a point from the known stations.
+///
+/// Like the state, this is only done when the nearest station is within `ZONE_MAX_MILES` and no
+/// station keeping a different time is within twice that distance. Zones with other names that
+/// keep the same time, like America/Detroit and America/New_York, don't count as different.
+pub fn known_station_zone(lat: f64, lon: f64) -> Option<Tz> {
+    nearest_agreed_station(lat, lon, ZONE_MAX_MILES, |left, right| {
+        same_clock(left.zone, right.zone)
+    })
+    .map(|stn| stn.zone)
+}
+
+/// The nearest station within `max_miles` of a point, unless a station that doesn't agree with
+/// it is within twice its distance.
+fn nearest_agreed_station(
+    lat: f64,
+    lon: f64,
+    max_miles: f64,
+    agree: impl Fn(&KnownStation, &KnownStation) -> bool,
+) -> Option<&'static KnownStation> {
+    let (nearest, distance) = nearest_known_station(lat, lon)?;
+    if distance > max_miles {
+        return None;
+    }
+
+    let contested = known_stations().iter().any(|stn| {
+        !agree(nearest, stn)
+            && haversine_miles((lat, lon), &(stn.lat, stn.lon)) <= 2.0 * distance.max(1.0)
+    });
+
+    (!contested).then_some(nearest)
+}
+
+/// Whether two zones have the same offset in both winter and summer.
+fn same_clock(left: Tz, right: Tz) -> bool {
+    [1, 7].into_iter().all(|month| {
+        let time = NaiveDate::from_ymd_opt(2025, month, 15)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+
+        left.offset_from_utc_datetime(&time).fix() == right.offset_from_utc_datetime(&time).fix()
+    })
+}
+
+fn parse_line(line: &'static str) -> Option<KnownStation> {
+    let mut cols = line.split(',').map(str::trim);
+
+    let id = cols.next()?;
+    let name = cols.next()?;
+    let state = StateProv::from_str(cols.next()?).ok()?;
+    let lat = f64::from_str(cols.next()?).ok()?;
+    let lon = f64::from_str(cols.next()?).ok()?;
+    let zone = Tz::from_str(cols.next()?).ok()?;
+
+    Some(KnownStation {
+        id,
+        name,
+        state,
+        lat,
+        lon,
+        zone,
+    })
+}
+
+#[cfg(test)]
+mod unit {
+    use super::*;
+
+    #[test]
+    fn test_stations_parse() {
+        let num_lines = STATIONS_CSV
+            .lines()
+            .skip(1)
+            .filter(|line| !line.trim().is_empty())
+            .count();
+
+        assert!(num_lines > 300);
+        for line in STATIONS_CSV.lines().skip(1).filter(|line| !line.trim().is_empty()) {
+            assert!(parse_line(line).is_some(), "Invalid station: {}", line);
+        }
+        assert_eq!(known_stations().len(), num_lines);
+    }
+
+    #[test]
+    fn test_nearest_known_station() {
+        let (stn, distance) = nearest_known_station(46.92, -114.09).unwrap();
+        assert_eq!(stn.id, "KMSO");
+        assert!(distance < 0.1);
+
+        let (stn, distance) = nearest_known_station(47.0, -114.0).unwrap();
+        assert_eq!(stn.id, "KMSO");
+        assert!(distance > 1.0);
+    }
+
+    #[test]
+    fn test_known_station_state() {
+        assert_eq!(known_station_state(46.95, -114.10), Some(StateProv::MT));
+
+        // Too far from any station.
+        assert_eq!(known_station_state(45.0, -111.0), None);
+
+        // Newark, NJ with JFK in New York 20 miles away.
+        assert_eq!(known_station_state(40.70, -74.16), Some(StateProv::NJ));
+        assert_eq!(known_station_state(40.67, -74.02), None);
+    }
+
+    #[test]
+    fn test_known_station_zone() {
+        assert_eq!(known_station_zone(46.95, -114.10), Some(chrono_tz::America::Denver));
+
+        // Too far from any station.
+        assert_eq!(known_station_zone(30.0, -140.0), None);
+
+        // Sidney, MT is on mountain time with Williston, ND on central time 41 miles away.
+        assert_eq!(known_station_zone(47.75, -104.15), Some(chrono_tz::America::Denver));
+        assert_eq!(known_station_zone(47.95, -103.90), None);
+
+        // Between Detroit and Toledo the zones have different names but keep the same time.
+        assert!(known_station_zone(41.90, -83.55).is_some());
+    }
+}
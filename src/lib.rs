@@ -3,13 +3,32 @@
 //
 // Public API
 //
-pub use crate::auto_download_list::AutoDownloadListDb;
+pub use crate::archive_index::{ArchiveIndex, IndexedFile};
+pub use crate::auto_download_list::{AutoDownloadEntry, AutoDownloadListDb, DownloadWindow};
+pub use crate::known_stations::{
+    KnownStation, known_station_state, known_station_zone, known_stations, nearest_known_station,
+};
+pub use crate::missing_url::MissingUrlDb;
+pub use crate::output::OutputFormat;
+pub use crate::site_registry::{RegisteredCoords, SiteRegistryDb};
+pub use crate::site_search::{MatchedField, SiteMatch, did_you_mean, search_sites};
 pub use crate::table_printer::TablePrinter;
-pub use crate::util::{bail, parse_date_string, site_id_to_station_num};
+pub use crate::time_zone::{LocalTimeZone, guess_time_zone, standard_offset};
+pub use crate::util::{
+    add_months, bail, errors_to_stderr, haversine_miles, parse_date_string, report_error,
+    site_id_to_station_num,
+};
 
 //
 // Internal only
 //
+mod archive_index;
 mod auto_download_list;
+mod known_stations;
+mod missing_url;
+mod output;
+mod site_registry;
+mod site_search;
 mod table_printer;
+mod time_zone;
 mod util;
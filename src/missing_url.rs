@@ -2,6 +2,7 @@ use bufkit_data::BufkitDataErr;
 use rusqlite::{Connection, OpenFlags};
 use std::path::Path;
 
+/// The URLs known to be missing from the remote archive, so they aren't requested again.
 pub struct MissingUrlDb {
     db_conn: Connection,
 }
@@ -28,7 +29,7 @@ impl MissingUrlDb {
     pub fn is_missing(&self, url: &str) -> Result<bool, BufkitDataErr> {
         let num_missing: i32 = self.db_conn.query_row(
             "SELECT COUNT(*) FROM missing WHERE url = ?1",
-            &[url],
+            [url],
             |row| row.get(0),
         )?;
 
@@ -37,8 +38,21 @@ impl MissingUrlDb {
 
     pub fn add_url(&self, url: &str) -> Result<(), BufkitDataErr> {
         self.db_conn
-            .execute("INSERT INTO missing (url) VALUES (?1)", &[url])?;
+            .execute("INSERT INTO missing (url) VALUES (?1)", [url])?;
 
         Ok(())
     }
+
+    /// Get the missing URLs for the files with a site id, in any model.
+    pub fn urls_for_site(&self, site_id: &str) -> Result<Vec<String>, BufkitDataErr> {
+        let pattern = format!("%\\_{}.buf", site_id.to_lowercase());
+
+        let mut stmt = self.db_conn.prepare(
+            "SELECT url FROM missing WHERE url LIKE ?1 ESCAPE '\\' ORDER BY url",
+        )?;
+
+        let urls: Result<Vec<String>, _> = stmt.query_map([&pattern], |row| row.get(0))?.collect();
+
+        Ok(urls?)
+    }
 }
@@ -0,0 +1,66 @@
+use serde::Serialize;
+use std::{error::Error, io, str::FromStr};
+
+/// How a command should print its results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Box drawn tables for people to read.
+    Table,
+    Csv,
+    Tsv,
+    Json,
+}
+
+impl OutputFormat {
+    /// The values accepted on the command line.
+    pub const NAMES: [&'static str; 4] = ["table", "csv", "tsv", "json"];
+
+    /// Write rows to stdout as CSV or TSV.
+    ///
+    /// Panics if the format isn't one of the delimited formats.
+    pub fn write_delimited(
+        self,
+        header: &[&str],
+        rows: &[Vec<String>],
+    ) -> Result<(), Box<dyn Error>> {
+        let delimiter = match self {
+            OutputFormat::Csv => b',',
+            OutputFormat::Tsv => b'\t',
+            _ => unreachable!("not a delimited format"),
+        };
+
+        let mut wtr = csv::WriterBuilder::new()
+            .delimiter(delimiter)
+            .from_writer(io::stdout());
+
+        wtr.write_record(header)?;
+        for row in rows {
+            wtr.write_record(row)?;
+        }
+        wtr.flush()?;
+
+        Ok(())
+    }
+
+    /// Write a value to stdout as pretty printed JSON.
+    pub fn write_json<T: Serialize + ?Sized>(value: &T) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer_pretty(io::stdout(), value)?;
+        println!();
+
+        Ok(())
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(OutputFormat::Table),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("Unknown output format: {}", s)),
+        }
+    }
+}
@@ -0,0 +1,382 @@
+use bufkit_data::{BufkitDataErr, Model, StationNumber};
+use chrono_tz::Tz;
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+use std::{path::Path, str::FromStr};
+
+/// Identifiers and locations for sites registered by hand before any data is downloaded.
+///
+/// The archive index only knows the ids and coordinates of a site through the files stored for
+/// it, so a site added with `bkam sites add` keeps them here until the first download. Ids and
+/// coordinates registered for a site with files correct the metadata in those files, and retired
+/// ids are no longer used. This also holds the IANA time zones of sites, the archive index only
+/// has room for a fixed UTC offset.
+pub struct SiteRegistryDb {
+    db_conn: Connection,
+}
+
+/// The location of a registered site.
+#[derive(Debug, Clone, Copy)]
+pub struct RegisteredCoords {
+    pub lat: f64,
+    pub lon: f64,
+    pub elevation_m: Option<f64>,
+}
+
+impl SiteRegistryDb {
+    pub fn open_or_create(root: &Path) -> Result<Self, BufkitDataErr> {
+        Self::open_with_flags(
+            root,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+        )
+    }
+
+    /// Open the registry only if it exists, for lookups that shouldn't create it as a side effect.
+    pub fn open(root: &Path) -> Result<Option<Self>, BufkitDataErr> {
+        if !root.join("site_registry.db").exists() {
+            return Ok(None);
+        }
+
+        Self::open_with_flags(root, OpenFlags::SQLITE_OPEN_READ_WRITE).map(Some)
+    }
+
+    fn open_with_flags(root: &Path, flags: OpenFlags) -> Result<Self, BufkitDataErr> {
+        let db_file = &root.join("site_registry.db");
+
+        let db_conn = Connection::open_with_flags(db_file, flags)?;
+
+        // An existing registry may be from before some of the tables were added.
+        db_conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS ids (
+                station_num INT  NOT NULL,
+                model       TEXT NOT NULL,
+                id          TEXT NOT NULL,
+                PRIMARY KEY (station_num, model, id)
+            );
+            CREATE TABLE IF NOT EXISTS coords (
+                station_num INT  PRIMARY KEY,
+                lat         REAL NOT NULL,
+                lon         REAL NOT NULL,
+                elevation_m REAL DEFAULT NULL
+            );
+            CREATE TABLE IF NOT EXISTS retired_ids (
+                station_num INT  NOT NULL,
+                model       TEXT NOT NULL,
+                id          TEXT NOT NULL,
+                PRIMARY KEY (station_num, model, id)
+            );
+            CREATE TABLE IF NOT EXISTS time_zones (
+                station_num INT  PRIMARY KEY,
+                zone        TEXT NOT NULL
+            );",
+        )?;
+
+        Ok(SiteRegistryDb { db_conn })
+    }
+
+    /// Get the station numbers of all the registered sites.
+    pub fn station_nums(&self) -> Result<Vec<StationNumber>, BufkitDataErr> {
+        let mut stmt = self.db_conn.prepare(
+            "SELECT station_num FROM ids UNION SELECT station_num FROM coords ORDER BY station_num",
+        )?;
+
+        let results: Result<Vec<StationNumber>, BufkitDataErr> = stmt
+            .query_map([], |row| row.get::<_, u32>(0))?
+            .map(|res| res.map(StationNumber::from).map_err(BufkitDataErr::Database))
+            .collect();
+
+        results
+    }
+
+    /// Register an id for a site, adding an id that is already registered moves it to the end.
+    pub fn add_id(
+        &self,
+        station_num: StationNumber,
+        model: Model,
+        id: &str,
+    ) -> Result<(), BufkitDataErr> {
+        let station_num: u32 = station_num.into();
+
+        // Delete it first so the new row is the last one added.
+        self.db_conn.execute(
+            "DELETE FROM ids WHERE station_num = ?1 AND model = ?2 AND id = ?3",
+            rusqlite::params![station_num, model.as_static_str(), id.to_uppercase()],
+        )?;
+        self.db_conn.execute(
+            "INSERT INTO ids (station_num, model, id) VALUES (?1, ?2, ?3)",
+            rusqlite::params![station_num, model.as_static_str(), id.to_uppercase()],
+        )?;
+        self.db_conn.execute(
+            "DELETE FROM retired_ids WHERE station_num = ?1 AND model = ?2 AND id = ?3",
+            rusqlite::params![station_num, model.as_static_str(), id.to_uppercase()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Stop using an id for a site, whether it was registered or came from the files.
+    pub fn retire_id(
+        &self,
+        station_num: StationNumber,
+        model: Model,
+        id: &str,
+    ) -> Result<(), BufkitDataErr> {
+        let station_num: u32 = station_num.into();
+
+        self.db_conn.execute(
+            "DELETE FROM ids WHERE station_num = ?1 AND model = ?2 AND id = ?3",
+            rusqlite::params![station_num, model.as_static_str(), id.to_uppercase()],
+        )?;
+        self.db_conn.execute(
+            "DELETE FROM retired_ids WHERE station_num = ?1 AND model = ?2 AND id = ?3",
+            rusqlite::params![station_num, model.as_static_str(), id.to_uppercase()],
+        )?;
+        self.db_conn.execute(
+            "INSERT INTO retired_ids (station_num, model, id) VALUES (?1, ?2, ?3)",
+            rusqlite::params![station_num, model.as_static_str(), id.to_uppercase()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Get the registered ids for a site, sorted by model and then in the order they were added.
+    pub fn ids_for(
+        &self,
+        station_num: StationNumber,
+    ) -> Result<Vec<(Model, String)>, BufkitDataErr> {
+        self.ids_from_table("ids", station_num)
+    }
+
+    /// Get the retired ids for a site, sorted by model and then in the order they were retired.
+    pub fn retired_ids_for(
+        &self,
+        station_num: StationNumber,
+    ) -> Result<Vec<(Model, String)>, BufkitDataErr> {
+        self.ids_from_table("retired_ids", station_num)
+    }
+
+    fn ids_from_table(
+        &self,
+        table: &str,
+        station_num: StationNumber,
+    ) -> Result<Vec<(Model, String)>, BufkitDataErr> {
+        let station_num: u32 = station_num.into();
+
+        let mut stmt = self.db_conn.prepare(&format!(
+            "SELECT model, id FROM {} WHERE station_num = ?1 ORDER BY model, rowid",
+            table
+        ))?;
+
+        let parse_row = |row: &rusqlite::Row| -> Result<(Model, String), BufkitDataErr> {
+            let model: String = row.get(0)?;
+            let id: String = row.get(1)?;
+
+            Ok((Model::from_str(&model)?, id))
+        };
+
+        let results: Result<Vec<(Model, String)>, BufkitDataErr> =
+            stmt.query_and_then([&station_num], parse_row)?.collect();
+
+        results
+    }
+
+    /// Find the site registered with this id for a model.
+    pub fn station_num_for_id(
+        &self,
+        id: &str,
+        model: Model,
+    ) -> Result<Option<StationNumber>, BufkitDataErr> {
+        let station_num: Option<u32> = self
+            .db_conn
+            .query_row(
+                "SELECT station_num FROM ids WHERE id = ?1 AND model = ?2",
+                rusqlite::params![id.to_uppercase(), model.as_static_str()],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(station_num.map(StationNumber::from))
+    }
+
+    /// Move the registered and retired ids, coordinates, and time zone of a site to a new station
+    /// number.
+    /// Coordinates and time zones already registered for the new station number are kept.
+    pub fn move_site(&self, from: StationNumber, to: StationNumber) -> Result<(), BufkitDataErr> {
+        let from: u32 = from.into();
+        let to: u32 = to.into();
+
+        for table in &["ids", "retired_ids", "coords", "time_zones"] {
+            self.db_conn.execute(
+                &format!(
+                    "UPDATE OR IGNORE {} SET station_num = ?2 WHERE station_num = ?1",
+                    table
+                ),
+                [&from, &to],
+            )?;
+            self.db_conn.execute(
+                &format!("DELETE FROM {} WHERE station_num = ?1", table),
+                [&from],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn set_coords(
+        &self,
+        station_num: StationNumber,
+        coords: RegisteredCoords,
+    ) -> Result<(), BufkitDataErr> {
+        let station_num: u32 = station_num.into();
+
+        self.db_conn.execute(
+            "INSERT OR REPLACE INTO coords (station_num, lat, lon, elevation_m)
+                VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![station_num, coords.lat, coords.lon, coords.elevation_m],
+        )?;
+
+        Ok(())
+    }
+
+    /// Remove the registered coordinates of a site.
+    pub fn remove_coords(&self, station_num: StationNumber) -> Result<(), BufkitDataErr> {
+        let station_num: u32 = station_num.into();
+
+        self.db_conn
+            .execute("DELETE FROM coords WHERE station_num = ?1", [&station_num])?;
+
+        Ok(())
+    }
+
+    /// Whether any site has registered coordinates.
+    pub fn has_coords(&self) -> Result<bool, BufkitDataErr> {
+        let count: u32 = self
+            .db_conn
+            .query_row("SELECT COUNT(*) FROM coords", [], |row| row.get(0))?;
+
+        Ok(count > 0)
+    }
+
+    pub fn coords_for(
+        &self,
+        station_num: StationNumber,
+    ) -> Result<Option<RegisteredCoords>, BufkitDataErr> {
+        let station_num: u32 = station_num.into();
+
+        let coords = self
+            .db_conn
+            .query_row(
+                "SELECT lat, lon, elevation_m FROM coords WHERE station_num = ?1",
+                [&station_num],
+                |row| {
+                    Ok(RegisteredCoords {
+                        lat: row.get(0)?,
+                        lon: row.get(1)?,
+                        elevation_m: row.get(2)?,
+                    })
+                },
+            )
+            .optional()?;
+
+        Ok(coords)
+    }
+
+    /// Set the IANA time zone of a site, or remove it with `None`.
+    pub fn set_time_zone(
+        &self,
+        station_num: StationNumber,
+        zone: Option<Tz>,
+    ) -> Result<(), BufkitDataErr> {
+        let station_num: u32 = station_num.into();
+
+        match zone {
+            Some(zone) => self.db_conn.execute(
+                "INSERT OR REPLACE INTO time_zones (station_num, zone) VALUES (?1, ?2)",
+                rusqlite::params![station_num, zone.name()],
+            )?,
+            None => self
+                .db_conn
+                .execute("DELETE FROM time_zones WHERE station_num = ?1", [&station_num])?,
+        };
+
+        Ok(())
+    }
+
+    pub fn time_zone_for(&self, station_num: StationNumber) -> Result<Option<Tz>, BufkitDataErr> {
+        let station_num: u32 = station_num.into();
+
+        let zone: Option<String> = self
+            .db_conn
+            .query_row(
+                "SELECT zone FROM time_zones WHERE station_num = ?1",
+                [&station_num],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        zone.map(|zone| {
+            Tz::from_str(&zone).map_err(|_| {
+                BufkitDataErr::GeneralError(format!("Invalid time zone in registry: {}", zone))
+            })
+        })
+        .transpose()
+    }
+}
+
+#[cfg(test)]
+mod unit {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_open_does_not_create() {
+        let tmp = TempDir::with_prefix("bfkmd-test-registry").unwrap();
+
+        assert!(SiteRegistryDb::open(tmp.path()).unwrap().is_none());
+        assert!(!tmp.path().join("site_registry.db").exists());
+
+        let stn = StationNumber::from(727730);
+        SiteRegistryDb::open_or_create(tmp.path())
+            .unwrap()
+            .add_id(stn, Model::GFS, "KMSO")
+            .unwrap();
+
+        let registry = SiteRegistryDb::open(tmp.path()).unwrap().unwrap();
+        assert_eq!(registry.station_num_for_id("KMSO", Model::GFS).unwrap(), Some(stn));
+    }
+
+    #[test]
+    fn test_ids_in_order_added() {
+        let tmp = TempDir::with_prefix("bfkmd-test-registry").unwrap();
+        let registry = SiteRegistryDb::open_or_create(tmp.path()).unwrap();
+        let stn = StationNumber::from(727730);
+
+        registry.add_id(stn, Model::NAM, "kmsx").unwrap();
+        registry.add_id(stn, Model::GFS, "KMSO").unwrap();
+        registry.add_id(stn, Model::NAM, "KMSO").unwrap();
+        registry.add_id(stn, Model::NAM, "KMSX").unwrap();
+
+        // Adding KMSX again makes it the last one added.
+        assert_eq!(
+            registry.ids_for(stn).unwrap(),
+            vec![
+                (Model::GFS, "KMSO".to_owned()),
+                (Model::NAM, "KMSO".to_owned()),
+                (Model::NAM, "KMSX".to_owned()),
+            ]
+        );
+        assert_eq!(registry.station_num_for_id("kmso", Model::GFS).unwrap(), Some(stn));
+
+        registry.retire_id(stn, Model::NAM, "KMSX").unwrap();
+        assert_eq!(registry.ids_for(stn).unwrap().len(), 2);
+        assert_eq!(
+            registry.retired_ids_for(stn).unwrap(),
+            vec![(Model::NAM, "KMSX".to_owned())]
+        );
+
+        // Adding it again takes it out of retirement.
+        registry.add_id(stn, Model::NAM, "KMSX").unwrap();
+        assert!(registry.retired_ids_for(stn).unwrap().is_empty());
+        assert_eq!(registry.ids_for(stn).unwrap()[2], (Model::NAM, "KMSX".to_owned()));
+    }
+}
@@ -0,0 +1,219 @@
+use crate::SiteRegistryDb;
+use bufkit_data::{Archive, BufkitDataErr, StateProv, StationNumber};
+use std::{collections::HashMap, fmt::Display};
+
+/// The smallest score for a site to count as a match.
+const MIN_SCORE: f64 = 0.5;
+
+/// The most suggestions to offer when a site can't be found.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// A site found by `search_sites`.
+#[derive(Debug, Clone)]
+pub struct SiteMatch {
+    pub station_num: StationNumber,
+    pub ids: Vec<String>,
+    pub name: Option<String>,
+    pub state: Option<StateProv>,
+    /// The field that matched best.
+    pub field: MatchedField,
+    /// From 0 to 1, where 1 is an exact match.
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchedField {
+    StationNumber,
+    Id,
+    Name,
+    Notes,
+}
+
+impl Display for MatchedField {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        let field = match self {
+            MatchedField::StationNumber => "station number",
+            MatchedField::Id => "id",
+            MatchedField::Name => "name",
+            MatchedField::Notes => "notes",
+        };
+
+        write!(f, "{}", field)
+    }
+}
+
+impl SiteMatch {
+    /// A short description for suggestions, e.g. KMSO (727730, Missoula).
+    pub fn short_description(&self) -> String {
+        let ids = if self.ids.is_empty() {
+            self.station_num.to_string()
+        } else {
+            self.ids.join("/")
+        };
+
+        match self.name {
+            Some(ref name) => format!("{} ({}, {})", ids, self.station_num, name),
+            None => format!("{} ({})", ids, self.station_num),
+        }
+    }
+}
+
+/// Find the sites that match a query by station number, id, name, or notes, best match first.
+///
+/// Ids and station numbers must match from the start, names and notes can match anywhere. Small
+/// typos are tolerated.
+pub fn search_sites(arch: &Archive, query: &str) -> Result<Vec<SiteMatch>, BufkitDataErr> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut ids: HashMap<StationNumber, Vec<String>> = arch
+        .station_summaries()?
+        .into_iter()
+        .map(|summary| (summary.station_num, summary.ids))
+        .collect();
+
+    let registry = SiteRegistryDb::open(arch.root())?;
+
+    let mut matches = vec![];
+    for site in arch.sites()? {
+        let mut site_ids = ids.remove(&site.station_num).unwrap_or_default();
+        if site_ids.is_empty()
+            && let Some(registry) = &registry
+        {
+            site_ids = registry
+                .ids_for(site.station_num)?
+                .into_iter()
+                .map(|(_, id)| id)
+                .collect();
+            site_ids.sort_unstable();
+            site_ids.dedup();
+        }
+
+        let stn_str = site.station_num.to_string();
+        let candidates = [
+            (MatchedField::StationNumber, score_code(&query, &stn_str, false)),
+            (
+                MatchedField::Id,
+                site_ids
+                    .iter()
+                    .map(|id| score_code(&query, &id.to_lowercase(), true))
+                    .fold(0.0, f64::max),
+            ),
+            (
+                MatchedField::Name,
+                site.name
+                    .as_deref()
+                    .map_or(0.0, |name| score_text(&query, &name.to_lowercase())),
+            ),
+            (
+                MatchedField::Notes,
+                site.notes
+                    .as_deref()
+                    .map_or(0.0, |notes| 0.7 * score_text(&query, &notes.to_lowercase())),
+            ),
+        ];
+
+        let (field, score) = candidates
+            .into_iter()
+            .fold((MatchedField::StationNumber, 0.0), |best, cand| {
+                if cand.1 > best.1 { cand } else { best }
+            });
+
+        if score >= MIN_SCORE {
+            matches.push(SiteMatch {
+                station_num: site.station_num,
+                ids: site_ids,
+                name: site.name,
+                state: site.state,
+                field,
+                score,
+            });
+        }
+    }
+
+    matches.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| Into::<u32>::into(a.station_num).cmp(&b.station_num.into()))
+    });
+
+    Ok(matches)
+}
+
+/// Suggestions to add to an error message when a site can't be found, empty if there are none.
+pub fn did_you_mean(arch: &Archive, query: &str) -> String {
+    let suggestions: Vec<String> = search_sites(arch, query)
+        .unwrap_or_default()
+        .iter()
+        .take(MAX_SUGGESTIONS)
+        .map(SiteMatch::short_description)
+        .collect();
+
+    if suggestions.is_empty() {
+        String::new()
+    } else {
+        format!(" Did you mean {}?", suggestions.join(", "))
+    }
+}
+
+/// Score a code like an id or station number, these have to match from the start. Typos are only
+/// tolerated for ids, a typo in a station number is a different station.
+fn score_code(query: &str, code: &str, allow_typos: bool) -> f64 {
+    if query == code {
+        1.0
+    } else if code.starts_with(query) {
+        0.6 + 0.3 * query.len() as f64 / code.len() as f64
+    } else if allow_typos {
+        0.85 * strsim::normalized_damerau_levenshtein(query, code)
+    } else {
+        0.0
+    }
+}
+
+/// Score free text like a name or notes, the query can match anywhere or match a word with typos.
+fn score_text(query: &str, text: &str) -> f64 {
+    if query == text {
+        1.0
+    } else if text.starts_with(query) {
+        0.95
+    } else if text.contains(query) {
+        0.9
+    } else {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .chain(std::iter::once(text))
+            .map(|word| strsim::jaro_winkler(query, word))
+            .filter(|&score| score >= 0.85)
+            .fold(0.0, f64::max)
+            * 0.8
+    }
+}
+
+#[cfg(test)]
+mod unit {
+    use super::*;
+
+    #[test]
+    fn test_score_code() {
+        assert_eq!(score_code("KMSO", "KMSO", true), 1.0);
+        assert!(score_code("KMS", "KMSO", true) > score_code("KM", "KMSO", true));
+        assert!(score_code("KMS", "KMSO", true) < 1.0);
+
+        // One swapped letter in an id is still a match, but not in a station number.
+        assert!(score_code("KMOS", "KMSO", true) > 0.5);
+        assert_eq!(score_code("727703", "727730", false), 0.0);
+        assert_eq!(score_code("MSO", "KMSO", false), 0.0);
+    }
+
+    #[test]
+    fn test_score_text() {
+        assert_eq!(score_text("missoula", "missoula"), 1.0);
+        assert!(score_text("miss", "missoula") > score_text("soula", "missoula"));
+        assert!(score_text("soula", "missoula") > score_text("misoula", "missoula"));
+        assert!(score_text("misoula", "missoula") > 0.0);
+        assert!(score_text("kalispel", "glacier park intl, kalispell") > 0.0);
+        assert_eq!(score_text("billings", "missoula"), 0.0);
+    }
+}
@@ -76,7 +76,7 @@ impl TablePrinter {
 
     pub fn add_row(&mut self, row_vals: Vec<String>) {
         debug_assert!(row_vals.len() == self.columns.len());
-        for (col, val) in self.columns.iter_mut().zip(row_vals.into_iter()) {
+        for (col, val) in self.columns.iter_mut().zip(row_vals) {
             col.push(val);
         }
     }
@@ -166,9 +166,9 @@ impl TablePrinter {
 
         if let Some(ref title) = self.title {
             // print top border
-            write!(
+            writeln!(
                 builder,
-                "\u{250c}{}\u{2510}\n",
+                "\u{250c}{}\u{2510}",
                 "\u{2500}".repeat(table_width)
             )?;
             // print title
@@ -205,7 +205,7 @@ impl TablePrinter {
                 "\u{2500}".repeat(table_width),
                 right_char
             )?;
-            for line in wrapper(&header, table_width) {
+            for line in wrapper(header, table_width) {
                 writeln!(builder, "\u{2502}{0:<1$}\u{2502}", line, table_width)?;
             }
 
@@ -305,7 +305,7 @@ impl TablePrinter {
         )?;
 
         if let Some(ref footer) = self.footer {
-            for line in wrapper(&footer, table_width) {
+            for line in wrapper(footer, table_width) {
                 writeln!(builder, "\u{2502}{0:<1$}\u{2502}", line, table_width)?;
             }
 
@@ -325,12 +325,12 @@ impl TablePrinter {
 fn wrapper(text: &str, table_width: usize) -> Vec<&str> {
     let mut to_ret: Vec<&str> = vec![];
 
-    let mut remaining = &text[..];
+    let mut remaining = text;
     while remaining.len() > table_width {
         let guess = &remaining[..table_width];
 
         let right_edge = guess
-            .find(|c| c == '\n')
+            .find('\n')
             .or_else(|| guess.rfind(char::is_whitespace))
             .unwrap_or(table_width);
         to_ret.push(&remaining[..right_edge]);
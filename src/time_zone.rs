@@ -0,0 +1,162 @@
+use crate::SiteRegistryDb;
+use bufkit_data::{BufkitDataErr, SiteInfo, StateProv};
+use chrono::{FixedOffset, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::{OffsetComponents, Tz};
+use std::fmt::Display;
+
+/// The time zone used to show local times for a site.
+///
+/// An IANA zone follows daylight saving time, sites without one fall back to the fixed UTC offset
+/// stored in the archive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LocalTimeZone {
+    Zone(Tz),
+    Fixed(FixedOffset),
+}
+
+impl LocalTimeZone {
+    /// Get the time zone for a site, preferring the IANA zone in the registry if there is one.
+    pub fn for_site(
+        registry: Option<&SiteRegistryDb>,
+        site: &SiteInfo,
+    ) -> Result<Option<Self>, BufkitDataErr> {
+        let zone = match registry {
+            Some(registry) => registry.time_zone_for(site.station_num)?,
+            None => None,
+        };
+
+        Ok(zone
+            .map(LocalTimeZone::Zone)
+            .or_else(|| site.time_zone.map(LocalTimeZone::Fixed)))
+    }
+
+    /// Format a UTC time as local time, `%Z` is the zone abbreviation, e.g. MDT.
+    pub fn format_local(&self, utc: NaiveDateTime, fmt: &str) -> String {
+        match self {
+            LocalTimeZone::Zone(tz) => tz.from_utc_datetime(&utc).format(fmt).to_string(),
+            LocalTimeZone::Fixed(offset) => offset.from_utc_datetime(&utc).format(fmt).to_string(),
+        }
+    }
+
+    /// Convert a UTC time to local time.
+    pub fn to_local(&self, utc: NaiveDateTime) -> NaiveDateTime {
+        match self {
+            LocalTimeZone::Zone(tz) => tz.from_utc_datetime(&utc).naive_local(),
+            LocalTimeZone::Fixed(offset) => offset.from_utc_datetime(&utc).naive_local(),
+        }
+    }
+}
+
+impl Display for LocalTimeZone {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            LocalTimeZone::Zone(tz) => write!(f, "{}", tz.name()),
+            LocalTimeZone::Fixed(offset) => write!(f, "{}", offset),
+        }
+    }
+}
+
+/// The standard time offset of a zone, ignoring daylight saving time.
+pub fn standard_offset(zone: Tz) -> FixedOffset {
+    let offset = zone.offset_from_utc_datetime(&Utc::now().naive_utc());
+    let base = offset.base_utc_offset();
+
+    FixedOffset::east_opt(base.num_seconds() as i32).unwrap_or_else(|| offset.fix())
+}
+
+/// Guess the IANA zone for a site from its state and fixed UTC offset, where the offset is the
+/// standard time offset. Returns `None` if no zone in the state has that offset.
+pub fn guess_time_zone(state: Option<StateProv>, offset: FixedOffset) -> Option<Tz> {
+    use chrono_tz::{America, Pacific};
+    use StateProv::*;
+
+    let candidates: &[Tz] = match state {
+        None => &[
+            America::New_York,
+            America::Chicago,
+            America::Denver,
+            America::Los_Angeles,
+            America::Anchorage,
+            Pacific::Honolulu,
+        ],
+        Some(state) => match state {
+            AZ => &[America::Phoenix],
+            AK => &[America::Anchorage, America::Adak],
+            HI => &[Pacific::Honolulu],
+            CO | MT | NM | UT | WY => &[America::Denver],
+            CA | NV | WA => &[America::Los_Angeles],
+            ID => &[America::Boise, America::Los_Angeles],
+            OR => &[America::Los_Angeles, America::Boise],
+            IN => &[America::Indiana::Indianapolis, America::Chicago],
+            KY => &[America::Kentucky::Louisville, America::Chicago],
+            MI => &[America::Detroit, America::Menominee],
+            FL | TN => &[America::New_York, America::Chicago],
+            KS | NE | ND | SD | TX => &[America::Chicago, America::Denver],
+            AL | AR | IL | IA | LA | MN | MS | MO | OK | WI => &[America::Chicago],
+            CT | DE | DC | GA | ME | MD | MA | NH | NJ | NY | NC | OH | PA | RI | SC | VT | VA
+            | WV => &[America::New_York],
+            PR => &[America::Puerto_Rico],
+            VI => &[America::St_Thomas],
+            AS => &[Pacific::Pago_Pago],
+            FM => &[Pacific::Chuuk, Pacific::Pohnpei],
+            MH => &[Pacific::Majuro],
+            MP => &[Pacific::Saipan],
+            PW => &[Pacific::Palau],
+        },
+    };
+
+    candidates
+        .iter()
+        .copied()
+        .find(|&zone| standard_offset(zone) == offset)
+}
+
+#[cfg(test)]
+mod unit {
+    use super::*;
+
+    use chrono::NaiveDate;
+    use chrono_tz::America;
+
+    fn hours(hours: i32) -> FixedOffset {
+        FixedOffset::east_opt(hours * 3600).unwrap()
+    }
+
+    #[test]
+    fn test_standard_offset() {
+        assert_eq!(standard_offset(America::Denver), hours(-7));
+        assert_eq!(standard_offset(America::Phoenix), hours(-7));
+        assert_eq!(standard_offset(America::New_York), hours(-5));
+    }
+
+    #[test]
+    fn test_guess_time_zone() {
+        use StateProv::*;
+
+        assert_eq!(guess_time_zone(Some(MT), hours(-7)), Some(America::Denver));
+        assert_eq!(guess_time_zone(Some(AZ), hours(-7)), Some(America::Phoenix));
+        assert_eq!(guess_time_zone(Some(ID), hours(-8)), Some(America::Los_Angeles));
+        assert_eq!(guess_time_zone(Some(ID), hours(-7)), Some(America::Boise));
+        assert_eq!(guess_time_zone(Some(TX), hours(-7)), Some(America::Denver));
+        assert_eq!(guess_time_zone(None, hours(-6)), Some(America::Chicago));
+
+        // No zone in the state has this offset.
+        assert_eq!(guess_time_zone(Some(MT), hours(-5)), None);
+    }
+
+    #[test]
+    fn test_local_time_zone() {
+        let utc = NaiveDate::from_ymd_opt(2024, 7, 1)
+            .unwrap()
+            .and_hms_opt(18, 0, 0)
+            .unwrap();
+
+        let zone = LocalTimeZone::Zone(America::Denver);
+        assert_eq!(zone.format_local(utc, "%H %Z"), "12 MDT");
+        assert_eq!(zone.to_string(), "America/Denver");
+
+        let fixed = LocalTimeZone::Fixed(hours(-7));
+        assert_eq!(fixed.to_local(utc), utc - chrono::Duration::hours(7));
+        assert_eq!(fixed.to_string(), "-07:00");
+    }
+}
@@ -1,9 +1,32 @@
 use bufkit_data::{Archive, Model, StationNumber};
-use chrono::{NaiveDate, NaiveDateTime};
-use std::{error::Error, fmt::Display};
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use crate::{SiteRegistryDb, did_you_mean};
+use std::{
+    error::Error,
+    fmt::Display,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use strum::IntoEnumIterator;
+
+/// Whether error messages go to stderr instead of stdout.
+static ERRORS_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Print error messages to stderr from now on, for commands that write their output to stdout.
+pub fn errors_to_stderr() {
+    ERRORS_TO_STDERR.store(true, Ordering::Relaxed);
+}
+
+/// Print an error message, on stdout unless `errors_to_stderr` was called.
+pub fn report_error(msg: &str) {
+    if ERRORS_TO_STDERR.load(Ordering::Relaxed) {
+        eprintln!("{}", msg);
+    } else {
+        println!("{}", msg);
+    }
+}
 
 pub fn bail(msg: &str) -> ! {
-    println!("{}", msg);
+    report_error(msg);
     ::std::process::exit(1);
 }
 
@@ -21,6 +44,9 @@ pub fn parse_date_string(dt_str: &str) -> NaiveDateTime {
     date.and_hms_opt(hour, 0, 0).unwrap()
 }
 
+/// Find the station number for a site id in any model.
+///
+/// The error message includes suggestions when the id is unknown or ambiguous.
 pub fn site_id_to_station_num(arch: &Archive, id: &str) -> Result<StationNumber, StrErr> {
     let mut value = 0u32;
     for &model in &[Model::GFS, Model::NAM, Model::NAM4KM] {
@@ -33,23 +59,66 @@ pub fn site_id_to_station_num(arch: &Archive, id: &str) -> Result<StationNumber,
             value = val;
         } else {
             return Err(StrErr {
-                msg: "ambigous id, multiple matches found",
+                msg: format!(
+                    "ambiguous id {}, multiple matches found, use a station number.{}",
+                    id,
+                    did_you_mean(arch, id)
+                ),
             });
         }
     }
 
+    // Sites registered by hand aren't in the index until their first download.
+    if value == 0
+        && let Some(stn_num) = SiteRegistryDb::open(arch.root())
+            .ok()
+            .flatten()
+            .and_then(|registry| {
+                Model::iter()
+                    .find_map(|model| registry.station_num_for_id(id, model).ok().flatten())
+            })
+    {
+        value = stn_num.into();
+    }
+
     if value == 0 {
         return Err(StrErr {
-            msg: "no matching station found for id",
+            msg: format!("no matching station found for id {}.{}", id, did_you_mean(arch, id)),
         });
     }
 
     Ok(bufkit_data::StationNumber::from(value))
 }
 
+/// The great circle distance in miles between two points.
+pub fn haversine_miles(from: (f64, f64), to: &(f64, f64)) -> f64 {
+    let (tlat, tlon) = from;
+    let (clat, clon) = to;
+
+    let dlat = (tlat - clat).to_radians();
+    let dlon = (tlon - clon).to_radians();
+
+    let lat = tlat.to_radians();
+    let clat = clat.to_radians();
+
+    let a = f64::powi(f64::sin(dlat / 2.0), 2)
+        + f64::powi(f64::sin(dlon / 2.0), 2) * f64::cos(lat) * f64::cos(clat);
+
+    let rad = 3958.761;
+    let c = 2.0 * f64::asin(f64::sqrt(a));
+    rad * c
+}
+
+/// Move a date on the first of a month forward or backward by a number of months.
+pub fn add_months(month: NaiveDate, num: i32) -> NaiveDate {
+    let months = month.year() * 12 + month.month0() as i32 + num;
+
+    NaiveDate::from_ymd_opt(months.div_euclid(12), months.rem_euclid(12) as u32 + 1, 1).unwrap()
+}
+
 #[derive(Debug)]
 pub struct StrErr {
-    pub msg: &'static str,
+    pub msg: String,
 }
 
 impl Display for StrErr {
@@ -59,3 +128,39 @@ impl Display for StrErr {
 }
 
 impl Error for StrErr {}
+
+#[cfg(test)]
+mod unit {
+    use super::*;
+
+    #[test]
+    fn test_haversine_miles() {
+        // Missoula to Kalispell, about 96 miles.
+        let kmso = (46.92, -114.09);
+        let kgpi = (48.31, -114.26);
+        let dist = haversine_miles(kmso, &kgpi);
+        assert!((dist - 96.4).abs() < 1.0, "{}", dist);
+        assert_eq!(dist, haversine_miles(kgpi, &kmso));
+
+        assert_eq!(haversine_miles(kmso, &kmso), 0.0);
+
+        // A quarter of the way around the earth along the equator.
+        let quarter = haversine_miles((0.0, 0.0), &(0.0, 90.0));
+        assert!((quarter - 6218.4).abs() < 1.0, "{}", quarter);
+
+        // Across the date line.
+        assert!(haversine_miles((0.0, 179.5), &(0.0, -179.5)) < 70.0);
+    }
+
+    #[test]
+    fn test_add_months() {
+        let month = |year, month| NaiveDate::from_ymd_opt(year, month, 1).unwrap();
+
+        assert_eq!(add_months(month(2024, 5), 0), month(2024, 5));
+        assert_eq!(add_months(month(2024, 5), 1), month(2024, 6));
+        assert_eq!(add_months(month(2024, 12), 1), month(2025, 1));
+        assert_eq!(add_months(month(2024, 1), -1), month(2023, 12));
+        assert_eq!(add_months(month(2024, 5), -11), month(2023, 6));
+        assert_eq!(add_months(month(2024, 5), 25), month(2026, 6));
+    }
+}